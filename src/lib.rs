@@ -0,0 +1,6809 @@
+use std::{
+    cell::RefCell,
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap, HashMap, HashSet, VecDeque},
+    env,
+    ffi::{CStr, CString, OsStr, OsString},
+    fs::{File, OpenOptions},
+    future::Future,
+    io::{BufRead, BufReader, BufWriter, Read, Write},
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::{
+            ffi::{OsStrExt, OsStringExt},
+            fs::{DirBuilderExt, MetadataExt},
+        },
+    },
+    path::{Path, PathBuf},
+    pin::Pin,
+    process::{Child, ChildStderr, Command, Stdio},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    task::{ready, Context, Poll},
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+
+use bumpalo::Bump;
+use futures_util::{Stream, StreamExt, TryStreamExt};
+#[cfg(feature = "progress")]
+use indicatif::{ProgressBar, ProgressStyle};
+use inotify::{EventMask, Inotify, WatchMask};
+#[cfg(target_os = "linux")]
+use io_uring::{opcode, types, IoUring};
+use memmap2::Mmap;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    runtime::Runtime,
+    signal::unix::SignalKind,
+    task::{JoinSet, LocalSet},
+};
+use wildmatch::WildMatch;
+
+/// Number of entries sorted at once by `read_dir_sorted_chunk`
+const SORT_CHUNK_SIZE: usize = 10_000;
+
+/// Pattern of the file names counted by `read_inotify_filter`
+const INOTIFY_PATTERN: &str = "*.txt";
+
+/// Buffer sizes, in bytes, benchmarked by `read_inotify_batch`
+const INOTIFY_BATCH_BUFFER_SIZES: [usize; 3] = [1024, 8096, 65536];
+
+/// Numbers of events processed at once by `read_inotify_chunked`
+const INOTIFY_CHUNK_SIZES: [usize; 4] = [1, 10, 100, 1000];
+
+/// Quiet time, in milliseconds, before `read_inotify_debounce` counts a file
+const INOTIFY_DEBOUNCE_MS: u64 = 10;
+
+/// Numbers of newest files found by `read_dir_topk`
+const TOPK_SIZES: [usize; 3] = [10, 100, 1000];
+
+/// Gap between the numbers of two consecutive file names with --sparse-names
+const SPARSE_NAMES_STRIDE: usize = 1000;
+
+/// Number of scans merged by `read_dir_sorted_merge`
+const MERGE_SCANS: usize = 4;
+
+/// Options for the thread creating files
+#[derive(Clone, Default)]
+pub struct CreateOptions {
+    /// use multi-byte UTF-8 characters in file names
+    pub unicode_names: bool,
+    /// write in place into existing files of the directory
+    pub overwrite: bool,
+    /// call `fsync` on each file before closing it
+    pub fsync: bool,
+    /// size, in bytes, pre-allocated with `fallocate` in each file
+    pub fallocate: Option<u64>,
+    /// create hard links to the first file instead of new files
+    pub hardlink: bool,
+    /// space the numbers in the file names by `SPARSE_NAMES_STRIDE`
+    pub sparse_names: bool,
+    /// write each file under a temporary name, then rename it to its final name
+    pub create_rename: bool,
+    /// number of files created in a burst, before pausing `burst_interval_ms`
+    pub burst_size: Option<usize>,
+    /// pause between two bursts, in milliseconds
+    pub burst_interval_ms: Option<u64>,
+    /// number of file names rewritten in turn, truncating the existing files
+    pub truncate: Option<usize>,
+}
+
+/// Command line options
+pub struct Options {
+    pub dir: String,
+    pub create: CreateOptions,
+    /// number of times each strategy is run
+    pub runs: usize,
+    /// print the histogram of inotify batch sizes
+    pub inotify_batch_stats: bool,
+    /// print the time between the oldest and newest file before the benchmarks
+    pub mtime_delta: bool,
+    /// print the number of entries by type before the benchmarks
+    pub count_types: bool,
+    /// create some files as root before the benchmarks, for `read_dir_sorted_by_uid`
+    pub sudo_create: bool,
+    /// print the number of entries by extension before the benchmarks
+    pub group_by_extension: bool,
+    /// print the total size of the files before the benchmarks
+    pub total_size: bool,
+    /// check that mtime order matches creation order before the benchmarks
+    pub mtime_check: bool,
+    /// print the time between adding an inotify watch and its first event
+    pub inotify_startup: bool,
+    /// print the latency of the first call of some strategies in a new process
+    pub cold_start: bool,
+    /// only run this strategy once and print its duration, in a process
+    /// started by --cold-start
+    pub cold_start_child: Option<String>,
+    /// check that inotify events arrive in the order the files are created
+    pub inotify_sequence_check: bool,
+    /// print the number of syscalls of each strategy, counted by strace
+    pub strace_counts: bool,
+    /// print the cache misses, instructions and page faults of each strategy
+    pub perf_counters: bool,
+    /// fraction of the metadata calls failing with a fabricated error
+    pub error_rate: Option<f64>,
+    /// I/O scheduling class and level of the benchmarks
+    pub io_priority: Option<IoPriority>,
+    /// only run these two strategies and compare their durations
+    pub compare: Option<(String, String)>,
+    /// file the report is written to, as JSON or CSV depending on its extension
+    pub output: Option<PathBuf>,
+    /// JSON report of a previous run to compare the median durations with
+    pub compare_baseline: Option<PathBuf>,
+    /// slowdown of the median duration, in percent, considered as a regression
+    pub regression_threshold: f64,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            dir: String::new(),
+            create: CreateOptions::default(),
+            runs: 1,
+            inotify_batch_stats: false,
+            mtime_delta: false,
+            count_types: false,
+            sudo_create: false,
+            group_by_extension: false,
+            total_size: false,
+            mtime_check: false,
+            inotify_startup: false,
+            cold_start: false,
+            cold_start_child: None,
+            inotify_sequence_check: false,
+            strace_counts: false,
+            perf_counters: false,
+            error_rate: None,
+            io_priority: None,
+            compare: None,
+            output: None,
+            compare_baseline: None,
+            regression_threshold: 10.0,
+        }
+    }
+}
+
+/// I/O scheduling class and level, parsed from `CLASS:LEVEL` with `CLASS` one of
+/// `RT`, `BE` or `IDLE`
+#[derive(Clone, Copy)]
+pub struct IoPriority {
+    class: u32,
+    level: u32,
+}
+
+impl std::str::FromStr for IoPriority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (class, level) = s.split_once(':').ok_or("expected CLASS:LEVEL")?;
+
+        // IOPRIO_CLASS_* values of linux/ioprio.h
+        let class = match class {
+            "RT" => 1,
+            "BE" => 2,
+            "IDLE" => 3,
+            _ => return Err(format!("unknown class {class}")),
+        };
+
+        let level = level
+            .parse()
+            .map_err(|_| format!("invalid level {level}"))?;
+        if level > 7 {
+            return Err(format!("level {level} is not in 0..=7"));
+        }
+
+        Ok(Self { class, level })
+    }
+}
+
+/// Sets the I/O priority of the calling thread, inherited by the threads it spawns
+#[cfg(target_os = "linux")]
+fn set_io_priority(priority: IoPriority) -> std::io::Result<()> {
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    // ioprio value: class in the 3 high bits (from bit 13), level in the low bits
+    const IOPRIO_CLASS_SHIFT: u32 = 13;
+
+    let ioprio = (priority.class << IOPRIO_CLASS_SHIFT) | priority.level;
+    // who 0 is the calling thread
+    let ret = unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, ioprio) };
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Returns the value following the option `name`, parsed as `T`
+fn parse_value<T: std::str::FromStr>(name: &str, value: Option<String>) -> T {
+    let Some(value) = value else {
+        panic!("missing value for {name}");
+    };
+
+    match value.parse() {
+        Ok(value) => value,
+        Err(_) => panic!("invalid value for {name}: {value}"),
+    }
+}
+
+/// Parses the command line arguments `args`, without the program name
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Options {
+    let mut options = Options::default();
+    let mut positional = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--unicode-names" => options.create.unicode_names = true,
+            "--overwrite" => options.create.overwrite = true,
+            "--fsync" => options.create.fsync = true,
+            "--hardlink" => options.create.hardlink = true,
+            "--sparse-names" => options.create.sparse_names = true,
+            "--create-rename" => options.create.create_rename = true,
+            "--burst-size" => options.create.burst_size = Some(parse_value(&arg, args.next())),
+            "--truncate" => options.create.truncate = Some(parse_value(&arg, args.next())),
+            "--burst-interval-ms" => {
+                options.create.burst_interval_ms = Some(parse_value(&arg, args.next()))
+            }
+            "--fallocate" => options.create.fallocate = Some(parse_value(&arg, args.next())),
+            "--inotify-batch-stats" => options.inotify_batch_stats = true,
+            "--mtime-delta" => options.mtime_delta = true,
+            "--count-types" => options.count_types = true,
+            "--sudo-create" => options.sudo_create = true,
+            "--group-by-extension" => options.group_by_extension = true,
+            "--total-size" => options.total_size = true,
+            "--mtime-check" => options.mtime_check = true,
+            "--inotify-startup" => options.inotify_startup = true,
+            "--cold-start" => options.cold_start = true,
+            "--cold-start-child" => options.cold_start_child = Some(parse_value(&arg, args.next())),
+            "--inotify-sequence-check" => options.inotify_sequence_check = true,
+            "--strace-counts" => options.strace_counts = true,
+            "--perf-counters" => options.perf_counters = true,
+            "--io-priority" => options.io_priority = Some(parse_value(&arg, args.next())),
+            "--error-rate" => options.error_rate = Some(parse_value(&arg, args.next())),
+            "--runs" => options.runs = parse_value(&arg, args.next()),
+            "--output" => options.output = Some(parse_value(&arg, args.next())),
+            "--compare-baseline" => options.compare_baseline = Some(parse_value(&arg, args.next())),
+            "--regression-threshold" => {
+                options.regression_threshold = parse_value(&arg, args.next())
+            }
+            _ if arg.starts_with("--") => panic!("invalid argument {arg}"),
+            _ => positional.push(arg),
+        }
+    }
+
+    // `DIR` or `compare STRATEGY_A STRATEGY_B DIR`
+    options.dir = match <[String; 1]>::try_from(positional) {
+        Ok([dir]) => dir,
+        Err(positional) => match <[String; 4]>::try_from(positional) {
+            Ok([command, a, b, dir]) if command == "compare" => {
+                options.compare = Some((a, b));
+                dir
+            }
+            _ => panic!("invalid number of arguments"),
+        },
+    };
+
+    if options.runs == 0 {
+        panic!("--runs must be at least 1");
+    }
+
+    if let Some(error_rate) = options.error_rate {
+        if !(0.0..=1.0).contains(&error_rate) {
+            panic!("--error-rate must be between 0 and 1");
+        }
+    }
+
+    if options.create.burst_size.is_some() != options.create.burst_interval_ms.is_some() {
+        panic!("--burst-size and --burst-interval-ms go together");
+    }
+
+    if options.create.burst_size == Some(0) {
+        panic!("--burst-size must be at least 1");
+    }
+
+    if options.create.create_rename && (options.create.hardlink || options.create.overwrite) {
+        panic!("--create-rename conflicts with --hardlink and --overwrite");
+    }
+
+    if options.create.truncate == Some(0) {
+        panic!("--truncate must be at least 1");
+    }
+
+    if options.create.truncate.is_some()
+        && (options.create.hardlink || options.create.overwrite || options.create.create_rename)
+    {
+        panic!("--truncate conflicts with --hardlink, --overwrite and --create-rename");
+    }
+
+    if options.create.hardlink && options.create.overwrite {
+        panic!("--hardlink can't be used with --overwrite");
+    }
+
+    if let Some(output) = &options.output {
+        if !matches!(
+            output.extension().and_then(OsStr::to_str),
+            Some("json" | "csv")
+        ) {
+            panic!("--output must end with .json or .csv");
+        }
+    }
+
+    if options.regression_threshold <= 0.0 {
+        panic!("--regression-threshold must be positive");
+    }
+
+    if options.compare.is_some() && options.runs < 2 {
+        panic!("compare needs --runs of at least 2");
+    }
+
+    options
+}
+
+/// Start of the names of the created files
+fn file_name_prefix(options: &CreateOptions) -> &'static str {
+    if options.unicode_names {
+        "fichier_"
+    } else {
+        "file"
+    }
+}
+
+fn file_name(count: usize, options: &CreateOptions) -> String {
+    let count = if options.sparse_names {
+        count * SPARSE_NAMES_STRIDE
+    } else {
+        count
+    };
+
+    if options.unicode_names {
+        format!("fichier_{count}_文件_📄.txt")
+    } else {
+        format!("file{count}.txt")
+    }
+}
+
+/// Creates the file `path` and writes its content
+fn write_file(path: &str, options: &CreateOptions) {
+    let mut file = if options.overwrite {
+        // no truncate: existing files are overwritten in place
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .unwrap()
+    } else if options.truncate.is_some() {
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .unwrap()
+    } else {
+        File::create(path).unwrap()
+    };
+    #[cfg(target_os = "linux")]
+    if let Some(size) = options.fallocate {
+        if unsafe { libc::fallocate(file.as_raw_fd(), 0, 0, size as libc::off_t) } != 0 {
+            panic!(
+                "Can't fallocate {path:?}: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+
+    file.write_all(b"Hello, world!").unwrap();
+    if options.fsync {
+        file.sync_all().unwrap();
+    }
+}
+
+fn create_files(rx: std::sync::mpsc::Receiver<()>, dir: String, options: CreateOptions) {
+    let mut count = 1;
+    // with --hardlink, the file all the next ones link to
+    let mut base = None;
+
+    loop {
+        match rx.try_recv() {
+            Ok(_) | Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                println!("Terminating.");
+                break;
+            }
+            Err(std::sync::mpsc::TryRecvError::Empty) => {}
+        }
+
+        let name = match options.truncate {
+            // once all the slots are created, start again from the first one
+            Some(slots) => file_name((count - 1) % slots + 1, &options),
+            None => file_name(count, &options),
+        };
+        let path = format!("{dir}/{name}");
+
+        let linked = match &base {
+            Some(base) => match std::fs::hard_link(base, &path) {
+                Ok(()) => true,
+                // the filesystem limit of links is reached, start a new base file
+                Err(e) if e.kind() == std::io::ErrorKind::TooManyLinks => false,
+                Err(e) => panic!("Can't link {path:?}: {e}"),
+            },
+            None => false,
+        };
+
+        if options.create_rename {
+            // hidden, so it's not mistaken for a delivered file
+            let tmp_path = format!("{dir}/.{name}.tmp");
+            write_file(&tmp_path, &options);
+            std::fs::rename(&tmp_path, &path).unwrap();
+        } else if !linked {
+            write_file(&path, &options);
+            if options.hardlink {
+                base = Some(path);
+            }
+        }
+
+        if let (Some(size), Some(interval_ms)) = (options.burst_size, options.burst_interval_ms) {
+            if count % size == 0 {
+                std::thread::sleep(Duration::from_millis(interval_ms));
+            }
+        }
+
+        count += 1;
+    }
+}
+
+/// Counts to `max` without any filesystem call: the cost of the loop of the
+/// other strategies, to subtract from their timings.
+fn read_dir_noop(max: usize) -> usize {
+    let mut count = 0;
+
+    while count < max {
+        // not optimized away into `count = max`
+        count = std::hint::black_box(count) + 1;
+    }
+
+    count
+}
+
+fn read_dir(dir: &String, max: usize) -> std::io::Result<()> {
+    let mut count = 0;
+
+    loop {
+        for entry in std::fs::read_dir(dir)? {
+            count += 1;
+            let _path = entry?.path();
+
+            if count == max {
+                break;
+            }
+        }
+
+        if count == max {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Entries per second read by the `read_dir_rate_limited` benchmark
+const RATE_LIMIT: u64 = 50_000;
+
+/// Same as `read_dir`, throttled to `rate` entries per second by a token bucket
+/// of `rate` tokens, like a slow consumer would.
+/// Returns the number of times it waited for a token.
+fn read_dir_rate_limited(dir: &str, max: usize, rate: u64) -> std::io::Result<usize> {
+    let rate = rate as f64;
+    let mut tokens = rate;
+    let mut refilled = Instant::now();
+    let mut waits = 0;
+    let mut count = 0;
+
+    loop {
+        for entry in std::fs::read_dir(dir)? {
+            loop {
+                // tokens added since the last refill, the bucket can't hold more than rate
+                let now = Instant::now();
+                tokens = (tokens + now.duration_since(refilled).as_secs_f64() * rate).min(rate);
+                refilled = now;
+
+                if tokens >= 1.0 {
+                    break;
+                }
+
+                // until the next token
+                std::thread::sleep(Duration::from_secs_f64((1.0 - tokens) / rate));
+                waits += 1;
+            }
+            tokens -= 1.0;
+
+            count += 1;
+            let _path = entry?.path();
+
+            if count == max {
+                break;
+            }
+        }
+
+        if count == max {
+            break;
+        }
+    }
+
+    Ok(waits)
+}
+
+/// Percentiles of the processing time of the entries of a directory
+#[derive(Debug, PartialEq)]
+struct LatencyStats {
+    p50: Duration,
+    p90: Duration,
+    p95: Duration,
+    p99: Duration,
+    p999: Duration,
+}
+
+impl LatencyStats {
+    /// Percentiles of `durations`, nearest rank, all zero without durations
+    fn new(mut durations: Vec<Duration>) -> Self {
+        durations.sort_unstable();
+
+        let percentile = |p: f64| {
+            let rank = (p * durations.len() as f64).ceil() as usize;
+            durations
+                .get(rank.saturating_sub(1))
+                .copied()
+                .unwrap_or_default()
+        };
+
+        Self {
+            p50: percentile(0.5),
+            p90: percentile(0.9),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            p999: percentile(0.999),
+        }
+    }
+}
+
+/// Same as `read_dir`, timing each entry from the moment it is returned by the
+/// iterator to the moment its path is built, to find the outliers that mean
+/// durations hide.
+fn measure_per_entry_latency(dir: &str, max: usize) -> std::io::Result<LatencyStats> {
+    let mut durations = Vec::with_capacity(max);
+
+    while durations.len() < max {
+        for entry in std::fs::read_dir(dir)? {
+            let start = Instant::now();
+            let _path = entry?.path();
+            durations.push(start.elapsed());
+
+            if durations.len() == max {
+                break;
+            }
+        }
+    }
+
+    Ok(LatencyStats::new(durations))
+}
+
+/// Iterator over the paths of the entries of `dir`, to be composed with the
+/// standard iterator adapters. An error opening `dir` is returned as the only item.
+fn dir_entries(dir: &Path) -> impl Iterator<Item = std::io::Result<PathBuf>> {
+    let (read_dir, error) = match std::fs::read_dir(dir) {
+        Ok(read_dir) => (Some(read_dir), None),
+        Err(e) => (None, Some(Err(e))),
+    };
+
+    error.into_iter().chain(
+        read_dir
+            .into_iter()
+            .flatten()
+            .map(|entry| entry.map(|e| e.path())),
+    )
+}
+
+/// Same as `read_dir`, using `dir_entries` with `take`
+fn read_dir_iter(dir: &str, max: usize) -> std::io::Result<()> {
+    let mut count = 0;
+
+    while count < max {
+        for path in dir_entries(Path::new(dir)).take(max - count) {
+            let _path = path?;
+            count += 1;
+        }
+    }
+
+    Ok(())
+}
+
+/// Same as `read_dir_iter`, only keeping files with a `txt` extension
+fn read_dir_iter_txt(dir: &str, max: usize) -> std::io::Result<()> {
+    let mut count = 0;
+
+    while count < max {
+        for path in dir_entries(Path::new(dir))
+            .filter(|p| {
+                p.as_ref()
+                    .map_or(true, |p| p.extension() == Some(OsStr::new("txt")))
+            })
+            .take(max - count)
+        {
+            let _path = path?;
+            count += 1;
+        }
+    }
+
+    Ok(())
+}
+
+/// Same as `read_dir`, showing the progress in a progress bar
+#[cfg(feature = "progress")]
+fn read_dir_with_progress(dir: &str, max: usize) -> std::io::Result<()> {
+    let bar = ProgressBar::new(max as u64);
+    bar.set_style(
+        ProgressStyle::with_template("{bar:40} {pos}/{len} [{elapsed_precise}] eta {eta}")
+            .expect("valid progress bar template"),
+    );
+
+    let mut count = 0;
+
+    loop {
+        for entry in std::fs::read_dir(dir)? {
+            count += 1;
+            let _path = entry?.path();
+            bar.inc(1);
+
+            if count == max {
+                break;
+            }
+        }
+
+        if count == max {
+            break;
+        }
+    }
+
+    bar.finish();
+
+    Ok(())
+}
+
+/// Fraction of the `metadata` calls failing with a fabricated `ENOENT`, set by
+/// `--error-rate`
+static ERROR_RATE: OnceLock<f64> = OnceLock::new();
+
+/// Number of errors fabricated by `metadata`
+static INJECTED_ERRORS: AtomicU64 = AtomicU64::new(0);
+
+/// Same as `std::fs::metadata`, failing with `ENOENT` without calling the
+/// filesystem for a fraction `ERROR_RATE` of the calls.
+fn metadata(path: &Path) -> std::io::Result<std::fs::Metadata> {
+    if let Some(&error_rate) = ERROR_RATE.get() {
+        if rand::random::<f64>() < error_rate {
+            INJECTED_ERRORS.fetch_add(1, Ordering::Relaxed);
+            return Err(std::io::Error::from_raw_os_error(libc::ENOENT));
+        }
+    }
+
+    std::fs::metadata(path)
+}
+
+/// Returns the modification time of `path` in nanoseconds since the epoch,
+/// printing the reason and returning `None` if it can't be read.
+fn modified_nanos(path: &Path) -> Option<u128> {
+    match metadata(path) {
+        Ok(metadata) => mtime_key(path, &metadata),
+        Err(e) => {
+            println!("Can't get metadata for file {path:?}: {e}");
+            None
+        }
+    }
+}
+
+/// Returns the modification time of `metadata`, the metadata of `path`, in
+/// nanoseconds since the epoch, printing the reason and returning `None` if it
+/// can't be read.
+fn mtime_key(path: &Path, metadata: &std::fs::Metadata) -> Option<u128> {
+    let duration = match metadata.modified() {
+        Ok(t) => t.duration_since(UNIX_EPOCH),
+        Err(e) => {
+            println!("Can't get modified time for file {path:?}: {e}");
+            return None;
+        }
+    };
+
+    match duration {
+        Ok(duration) => Some(duration.as_nanos()),
+        Err(e) => {
+            println!("Can't get time duration for file {path:?}: {e}");
+            None
+        }
+    }
+}
+
+/// Returns the time between the oldest and the newest file of `dir`
+fn read_dir_mtime_delta(dir: &str) -> std::io::Result<Duration> {
+    let mut oldest = u128::MAX;
+    let mut newest = u128::MIN;
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if let Some(duration_nano) = modified_nanos(&path) {
+            oldest = oldest.min(duration_nano);
+            newest = newest.max(duration_nano);
+        }
+    }
+
+    if oldest > newest {
+        // no file
+        return Ok(Duration::ZERO);
+    }
+
+    Ok(Duration::from_nanos((newest - oldest) as u64))
+}
+
+/// Number of files created by `measure_mtime_resolution`
+const MTIME_RESOLUTION_FILES: usize = 100;
+
+/// Creates `MTIME_RESOLUTION_FILES` files in `dir` in rapid succession and
+/// returns the smallest non-zero difference between consecutive mtimes, or
+/// zero if all the files got the same mtime.
+fn measure_mtime_resolution(dir: &str) -> Duration {
+    let mut mtimes = Vec::with_capacity(MTIME_RESOLUTION_FILES);
+
+    for i in 0..MTIME_RESOLUTION_FILES {
+        let path = PathBuf::from(format!("{dir}/probe{i}"));
+        std::fs::write(&path, "toto").unwrap();
+        if let Some(duration_nano) = modified_nanos(&path) {
+            mtimes.push(duration_nano);
+        }
+    }
+
+    let resolution = mtimes
+        .windows(2)
+        .map(|pair| pair[1].abs_diff(pair[0]))
+        .filter(|&delta| delta > 0)
+        .min()
+        .unwrap_or(0);
+
+    Duration::from_nanos(resolution as u64)
+}
+
+/// Checks that the mtime of the files of `dir`, ordered by the number in their
+/// name, is non-decreasing: `read_dir_sorted` assumes mtime order is creation
+/// order. Returns `Ok(false)` if there are less than 2 files to compare, and
+/// `Err` with the first pair of files out of order.
+fn read_dir_mtime_monotonic_check(dir: &str) -> Result<bool, String> {
+    let mut files = BTreeMap::new();
+
+    let entries = std::fs::read_dir(dir).map_err(|e| format!("can't read {dir}: {e}"))?;
+    for entry in entries {
+        let path = entry.map_err(|e| format!("can't read {dir}: {e}"))?.path();
+
+        let number = name_number(&path);
+        if number == NO_NUMBER_KEY {
+            continue;
+        }
+
+        if let Some(duration_nano) = modified_nanos(&path) {
+            files.insert(number, (path, duration_nano));
+        }
+    }
+
+    if files.len() < 2 {
+        return Ok(false);
+    }
+
+    let files: Vec<_> = files.into_values().collect();
+    for pair in files.windows(2) {
+        let [(previous, previous_mtime), (path, mtime)] = pair else {
+            unreachable!();
+        };
+
+        if mtime < previous_mtime {
+            return Err(format!(
+                "{} is older than {}",
+                path.display(),
+                previous.display()
+            ));
+        }
+    }
+
+    Ok(true)
+}
+
+/// Sum of the sizes of the regular files of `dir`, symlinks are not followed
+fn total_dir_size(dir: &str) -> std::io::Result<u64> {
+    let mut total = 0;
+
+    for entry in std::fs::read_dir(dir)? {
+        let metadata = entry?.metadata()?;
+        if metadata.is_file() {
+            total += metadata.len();
+        }
+    }
+
+    Ok(total)
+}
+
+/// Limits of the `read_dir_quota_check` benchmark
+const QUOTA_MAX_INODES: u64 = 100_000;
+const QUOTA_MAX_BYTES: u64 = 1024 * 1024;
+
+/// Limit exceeded by a directory
+#[derive(Debug, PartialEq)]
+enum QuotaLimit {
+    Inodes,
+    Bytes,
+}
+
+/// Result of `read_dir_quota_check`
+struct QuotaCheckResult {
+    /// inodes available to unprivileged users on the filesystem, from `statvfs`
+    free_inodes: u64,
+    /// number of entries scanned, including the one exceeding a limit
+    inodes: u64,
+    /// size of the regular files scanned
+    bytes: u64,
+    /// limit exceeded and entry that exceeded it
+    exceeded: Option<(QuotaLimit, PathBuf)>,
+}
+
+/// Scans `dir` until it holds more than `max_inodes` entries or `max_bytes` of
+/// regular files, like quota enforcement tools aborting early.
+/// Symlinks are not followed.
+fn read_dir_quota_check(
+    dir: &str,
+    max_inodes: u64,
+    max_bytes: u64,
+) -> std::io::Result<QuotaCheckResult> {
+    let c_dir = CString::new(dir)?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_dir.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    let mut result = QuotaCheckResult {
+        free_inodes: stat.f_favail as u64,
+        inodes: 0,
+        bytes: 0,
+        exceeded: None,
+    };
+
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+
+        result.inodes += 1;
+        if metadata.is_file() {
+            result.bytes += metadata.len();
+        }
+
+        if result.inodes > max_inodes {
+            result.exceeded = Some((QuotaLimit::Inodes, entry.path()));
+        } else if result.bytes > max_bytes {
+            result.exceeded = Some((QuotaLimit::Bytes, entry.path()));
+        }
+
+        if result.exceeded.is_some() {
+            break;
+        }
+    }
+
+    Ok(result)
+}
+
+/// Formats `bytes` with the largest binary unit keeping at least 1, e.g. `1.5 MiB`
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// Number of entries of a directory by type
+#[derive(Default)]
+struct EntryTypeCounts {
+    files: usize,
+    dirs: usize,
+    symlinks: usize,
+    other: usize,
+}
+
+/// Counts the entries of `dir` by type, symlinks are not followed
+fn count_entry_types(dir: &str) -> std::io::Result<EntryTypeCounts> {
+    let mut counts = EntryTypeCounts::default();
+
+    for entry in std::fs::read_dir(dir)? {
+        let file_type = entry?.file_type()?;
+
+        if file_type.is_file() {
+            counts.files += 1;
+        } else if file_type.is_dir() {
+            counts.dirs += 1;
+        } else if file_type.is_symlink() {
+            counts.symlinks += 1;
+        } else {
+            counts.other += 1;
+        }
+    }
+
+    Ok(counts)
+}
+
+/// Groups the paths of the entries of `dir` by extension, the entries without
+/// extension under the empty one
+fn group_by_extension(dir: &str) -> std::io::Result<HashMap<OsString, Vec<PathBuf>>> {
+    let mut groups: HashMap<OsString, Vec<PathBuf>> = HashMap::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let extension = path.extension().unwrap_or_default().to_os_string();
+        groups.entry(extension).or_default().push(path);
+    }
+
+    Ok(groups)
+}
+
+fn read_dir_sorted(dir: &str, max: usize) -> std::io::Result<BTreeMap<u128, VecDeque<PathBuf>>> {
+    // btreemap to order files by date
+    read_dir_sorted_by(dir, max, mtime_key)
+}
+
+/// Same as `read_dir_sorted`, ordered by the key returned by `key` for each
+/// path and its metadata. Entries without key are skipped.
+fn read_dir_sorted_by<K: Ord>(
+    dir: &str,
+    max: usize,
+    key: impl Fn(&Path, &std::fs::Metadata) -> Option<K>,
+) -> std::io::Result<BTreeMap<K, VecDeque<PathBuf>>> {
+    let mut ordered_files = BTreeMap::new();
+    read_dir_sorted_into(dir, max, &mut ordered_files, key, |path| path)?;
+    Ok(ordered_files)
+}
+
+/// Reads `max` entries of `dir`, reading it again if needed, and inserts them
+/// in `ordered_files` under the key returned by `key`, stored as returned by
+/// `value`. Entries whose metadata or key can't be read are skipped, but
+/// counted in the `max` entries.
+fn read_dir_sorted_into<K: Ord, V>(
+    dir: &str,
+    max: usize,
+    ordered_files: &mut BTreeMap<K, VecDeque<V>>,
+    key: impl Fn(&Path, &std::fs::Metadata) -> Option<K>,
+    mut value: impl FnMut(PathBuf) -> V,
+) -> std::io::Result<()> {
+    let mut count = 0;
+
+    while count < max {
+        for entry in std::fs::read_dir(dir)? {
+            // before the entry is processed: a skipped entry still counts
+            if count == max {
+                break;
+            }
+            count += 1;
+
+            let path = entry?.path();
+
+            let metadata = match metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    println!("Can't get metadata for file {path:?}: {e}");
+                    continue;
+                }
+            };
+
+            // insert files, automatically ordered by key
+            let Some(key) = key(&path, &metadata) else {
+                continue;
+            };
+
+            ordered_files
+                .entry(key)
+                .or_default()
+                .push_front(value(path));
+        }
+    }
+
+    Ok(())
+}
+
+/// Same as `read_dir_sorted` in two passes: all the paths are read first, then
+/// the metadata of each of them, instead of alternating readdir and stat.
+fn read_dir_sorted_two_pass(
+    dir: &str,
+    max: usize,
+) -> std::io::Result<BTreeMap<u128, VecDeque<PathBuf>>> {
+    let paths = collect_paths(dir, max)?;
+
+    let mut ordered_files: BTreeMap<u128, VecDeque<PathBuf>> = BTreeMap::new();
+    for path in paths {
+        let Some(duration_nano) = modified_nanos(&path) else {
+            continue;
+        };
+
+        ordered_files
+            .entry(duration_nano)
+            .or_default()
+            .push_front(path);
+    }
+
+    Ok(ordered_files)
+}
+
+/// Serializes a map of `read_dir_sorted` with bincode, e.g. to send it to
+/// another process. Paths must be valid UTF-8.
+fn serialize_sorted_result(result: &BTreeMap<u128, VecDeque<PathBuf>>) -> Vec<u8> {
+    bincode::serialize(result).expect("paths are valid UTF-8")
+}
+
+/// Deserializes a map serialized by `serialize_sorted_result`
+fn deserialize_sorted_result(bytes: &[u8]) -> BTreeMap<u128, VecDeque<PathBuf>> {
+    bincode::deserialize(bytes).expect("bytes of serialize_sorted_result")
+}
+
+/// Adds to `seen`, sorted by mtime like `read_dir_sorted`, up to `max_new`
+/// entries of `dir` not already in it, for scans polled again and again.
+/// Returns the number of new entries.
+fn read_dir_sorted_incremental(
+    dir: &str,
+    seen: &mut BTreeMap<u128, VecDeque<PathBuf>>,
+    max_new: usize,
+) -> std::io::Result<usize> {
+    let known: HashSet<PathBuf> = seen.values().flatten().cloned().collect();
+    let mut count = 0;
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if known.contains(&path) {
+            continue;
+        }
+
+        let Some(duration_nano) = modified_nanos(&path) else {
+            continue;
+        };
+
+        seen.entry(duration_nano).or_default().push_front(path);
+        count += 1;
+
+        if count == max_new {
+            break;
+        }
+    }
+
+    Ok(count)
+}
+
+thread_local! {
+    /// Map reused by all the calls of `read_dir_sorted_thread_local` of a thread
+    static SORT_BUFFER: RefCell<BTreeMap<u128, VecDeque<PathBuf>>> =
+        const { RefCell::new(BTreeMap::new()) };
+}
+
+/// Same as `read_dir_sorted`, filling a thread-local map cleared at each call
+/// instead of allocating a new one. `BTreeMap::clear` frees the nodes though, so
+/// only the map itself is reused.
+/// Returns the number of sorted files.
+fn read_dir_sorted_thread_local(dir: &str, max: usize) -> std::io::Result<usize> {
+    SORT_BUFFER.with_borrow_mut(|ordered_files| {
+        ordered_files.clear();
+        read_dir_sorted_into(dir, max, ordered_files, mtime_key, |path| path)?;
+        Ok(ordered_files.values().map(VecDeque::len).sum())
+    })
+}
+
+/// Mtime of each path of a sorted map, to find its key without a scan
+type MtimeIndex = HashMap<PathBuf, u128>;
+
+/// Same as `read_dir_sorted`, also returning the index of the mtime key of
+/// each path, to find where a given file is in the sorted queue.
+fn read_dir_sorted_with_index(
+    dir: &str,
+    max: usize,
+) -> std::io::Result<(BTreeMap<u128, VecDeque<PathBuf>>, MtimeIndex)> {
+    let ordered_files = read_dir_sorted(dir, max)?;
+
+    let index = ordered_files
+        .iter()
+        .flat_map(|(duration_nano, paths)| paths.iter().map(|path| (path.clone(), *duration_nano)))
+        .collect();
+
+    Ok((ordered_files, index))
+}
+
+/// Same as `read_dir_sorted`, writing the sorted paths to `output`, one per line,
+/// as a real consumer of the sorted result would.
+fn read_dir_sorted_stream_to_file(dir: &str, max: usize, output: &Path) -> std::io::Result<()> {
+    let ordered_files = read_dir_sorted(dir, max)?;
+
+    let mut writer = BufWriter::new(File::create(output)?);
+    for path in ordered_files.values().flatten() {
+        writer.write_all(path.as_os_str().as_bytes())?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()
+}
+
+/// Key of the files without number in `read_dir_sparse_sorted`, sorted last
+const NO_NUMBER_KEY: u64 = u64::MAX;
+
+/// Returns the last number found in the name of `path` without its extension,
+/// e.g. 42 for `file_0000042.txt`, or `NO_NUMBER_KEY`.
+fn name_number(path: &Path) -> u64 {
+    let Some(stem) = path.file_stem() else {
+        return NO_NUMBER_KEY;
+    };
+
+    let stem = stem.as_bytes();
+    let Some(end) = stem.iter().rposition(u8::is_ascii_digit) else {
+        return NO_NUMBER_KEY;
+    };
+    let start = stem[..end]
+        .iter()
+        .rposition(|b| !b.is_ascii_digit())
+        .map_or(0, |i| i + 1);
+
+    // only ASCII digits, the conversion can only fail on overflow
+    std::str::from_utf8(&stem[start..=end])
+        .ok()
+        .and_then(|digits| digits.parse().ok())
+        .unwrap_or(NO_NUMBER_KEY)
+}
+
+/// Same as `read_dir_sorted`, ordered by the number in the file names instead of
+/// the date, no metadata needed.
+fn read_dir_sparse_sorted(
+    dir: &str,
+    max: usize,
+) -> std::io::Result<BTreeMap<u64, VecDeque<PathBuf>>> {
+    let mut ordered_files: BTreeMap<u64, VecDeque<PathBuf>> = BTreeMap::new();
+
+    let mut count = 0;
+
+    loop {
+        for entry in std::fs::read_dir(dir)? {
+            count += 1;
+            let path = entry?.path();
+
+            ordered_files
+                .entry(name_number(&path))
+                .or_default()
+                .push_front(path);
+
+            if count == max {
+                break;
+            }
+        }
+
+        if count == max {
+            break;
+        }
+    }
+
+    Ok(ordered_files)
+}
+
+/// Same as `read_dir_sorted`, files with the same mtime being ordered by inode
+/// number instead of read order, so the order doesn't depend on the filesystem.
+/// The key being unique per file, an entry read several times is kept once.
+fn read_dir_sorted_tiebreak(
+    dir: &str,
+    max: usize,
+) -> std::io::Result<BTreeMap<(u128, u64), PathBuf>> {
+    let ordered_files = read_dir_sorted_by(dir, max, |path, metadata| {
+        Some((mtime_key(path, metadata)?, metadata.ino()))
+    })?;
+
+    // the copies of a file read several times share its key
+    Ok(ordered_files
+        .into_iter()
+        .filter_map(|(key, mut paths)| Some((key, paths.pop_front()?)))
+        .collect())
+}
+
+/// Same as `read_dir_sorted`, ordered by number of hard links, most linked
+/// files first.
+fn read_dir_sorted_by_nlink(
+    dir: &str,
+    max: usize,
+) -> std::io::Result<BTreeMap<Reverse<u64>, VecDeque<PathBuf>>> {
+    read_dir_sorted_by(dir, max, |_, metadata| Some(Reverse(metadata.nlink())))
+}
+
+/// Same as `read_dir_sorted`, ordered by owner user ID, grouping the files of
+/// each user of directories shared by several of them like `/tmp`.
+fn read_dir_sorted_by_uid(
+    dir: &str,
+    max: usize,
+) -> std::io::Result<BTreeMap<u32, VecDeque<PathBuf>>> {
+    read_dir_sorted_by(dir, max, |_, metadata| Some(metadata.uid()))
+}
+
+/// Number of files created as root with --sudo-create
+const SUDO_CREATE_FILES: usize = 100;
+
+/// Creates `count` empty files owned by root in `dir` with `sudo touch`, so
+/// `read_dir_sorted_by_uid` has more than one owner to sort. `sudo` must not ask
+/// for a password.
+fn create_files_as_root(dir: &str, count: usize) -> std::io::Result<()> {
+    let status = Command::new("sudo")
+        .args(["-n", "touch"])
+        .args((0..count).map(|i| format!("{dir}/root{i}.txt")))
+        .status()?;
+
+    if !status.success() {
+        return Err(std::io::Error::other(format!(
+            "sudo touch failed: {status}"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Same as `read_dir_sorted`, ordered by number of allocated 512-byte blocks,
+/// the disk usage, which differs from the size for sparse or pre-allocated files.
+fn read_dir_sorted_by_blocks(
+    dir: &str,
+    max: usize,
+) -> std::io::Result<BTreeMap<u64, VecDeque<PathBuf>>> {
+    read_dir_sorted_by(dir, max, |_, metadata| Some(metadata.blocks()))
+}
+
+/// Same as `read_dir_sorted`, ordered by length in bytes of the file name, like
+/// storage allocating directory slots proportional to the name length.
+fn read_dir_sorted_by_name_length(
+    dir: &str,
+    max: usize,
+) -> std::io::Result<BTreeMap<usize, VecDeque<PathBuf>>> {
+    read_dir_sorted_by(dir, max, |path, _| Some(path.file_name()?.len()))
+}
+
+/// Same as `read_dir_sorted`, the paths being copied in a `bumpalo` arena freed
+/// at once at the end instead of one allocation per path.
+/// Returns the number of sorted files.
+fn read_dir_sorted_bump(dir: &str, max: usize) -> std::io::Result<usize> {
+    let arena = Bump::new();
+    // btreemap to order files by date, paths stored as bytes in the arena
+    let mut ordered_files: BTreeMap<u128, VecDeque<&[u8]>> = BTreeMap::new();
+    read_dir_sorted_into(dir, max, &mut ordered_files, mtime_key, |path| {
+        &*arena.alloc_slice_copy(path.as_os_str().as_bytes())
+    })?;
+
+    // paths are only converted back when used
+    let mut sorted = 0;
+    for path in ordered_files.values().flatten() {
+        let _path = Path::new(OsStr::from_bytes(path));
+        sorted += 1;
+    }
+
+    Ok(sorted)
+}
+
+/// Same as `read_dir_sorted`, but only keeps `chunk_size` entries in memory:
+/// each chunk is sorted by date and processed before the next one is collected.
+/// Returns the number of chunks needed to read `max` files.
+fn read_dir_sorted_chunk(dir: &str, max: usize, chunk_size: usize) -> std::io::Result<usize> {
+    let mut chunk: Vec<(u128, PathBuf)> = Vec::with_capacity(chunk_size);
+    let mut chunks = 0;
+    let mut count = 0;
+
+    let mut process_chunk = |chunk: &mut Vec<(u128, PathBuf)>| {
+        chunk.sort_unstable_by_key(|(duration_nano, _)| *duration_nano);
+        // sorted files are not used, only the sort is measured
+        chunk.clear();
+        chunks += 1;
+    };
+
+    loop {
+        for entry in std::fs::read_dir(dir)? {
+            count += 1;
+            let path = entry?.path();
+
+            if let Some(duration_nano) = modified_nanos(&path) {
+                chunk.push((duration_nano, path));
+            }
+
+            if chunk.len() == chunk_size {
+                process_chunk(&mut chunk);
+            }
+
+            if count == max {
+                break;
+            }
+        }
+
+        if count == max {
+            break;
+        }
+    }
+
+    if !chunk.is_empty() {
+        process_chunk(&mut chunk);
+    }
+
+    Ok(chunks)
+}
+
+/// Writes one entry of a sorted run: date, path length, path bytes
+fn write_run_entry(
+    writer: &mut impl Write,
+    duration_nano: u128,
+    path: &Path,
+) -> std::io::Result<()> {
+    let bytes = path.as_os_str().as_bytes();
+    writer.write_all(&duration_nano.to_le_bytes())?;
+    writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    writer.write_all(bytes)
+}
+
+/// Reads the next entry written by `write_run_entry`, `None` at the end of the run
+fn read_run_entry(reader: &mut impl Read) -> std::io::Result<Option<(u128, PathBuf)>> {
+    let mut duration_nano = [0; 16];
+    match reader.read_exact(&mut duration_nano) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+    let mut bytes = vec![0; u32::from_le_bytes(len) as usize];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(Some((
+        u128::from_le_bytes(duration_nano),
+        PathBuf::from(OsString::from_vec(bytes)),
+    )))
+}
+
+/// External merge sort for directories too large to be sorted in memory: each
+/// chunk of `chunk_size` entries is sorted and written as a run file in
+/// `temp_dir`, then all the runs are merged with a k-way merge.
+/// Returns the number of runs.
+fn read_dir_lazy_sorted(
+    dir: &str,
+    max: usize,
+    temp_dir: &Path,
+    chunk_size: usize,
+) -> std::io::Result<usize> {
+    let mut run_files = Vec::new();
+    let mut chunk: Vec<(u128, PathBuf)> = Vec::with_capacity(chunk_size);
+    let mut count = 0;
+
+    let mut write_run = |chunk: &mut Vec<(u128, PathBuf)>| -> std::io::Result<()> {
+        chunk.sort_unstable_by_key(|(duration_nano, _)| *duration_nano);
+
+        let run_file = temp_dir.join(format!("run{}", run_files.len()));
+        let mut writer = BufWriter::new(File::create(&run_file)?);
+        for (duration_nano, path) in chunk.drain(..) {
+            write_run_entry(&mut writer, duration_nano, &path)?;
+        }
+        writer.flush()?;
+
+        run_files.push(run_file);
+        Ok(())
+    };
+
+    while count < max {
+        for entry in std::fs::read_dir(dir)? {
+            count += 1;
+            let path = entry?.path();
+
+            if let Some(duration_nano) = modified_nanos(&path) {
+                chunk.push((duration_nano, path));
+            }
+
+            if chunk.len() == chunk_size {
+                write_run(&mut chunk)?;
+            }
+
+            if count == max {
+                break;
+            }
+        }
+    }
+
+    if !chunk.is_empty() {
+        write_run(&mut chunk)?;
+    }
+
+    let mut readers = run_files
+        .iter()
+        .map(|run_file| File::open(run_file).map(BufReader::new))
+        .collect::<std::io::Result<Vec<_>>>()?;
+
+    // min-heap of the next entry of each run, with the index of its run
+    let mut heap = BinaryHeap::new();
+    for (i, reader) in readers.iter_mut().enumerate() {
+        if let Some(entry) = read_run_entry(reader)? {
+            heap.push(Reverse((entry, i)));
+        }
+    }
+
+    while let Some(Reverse(((_duration_nano, _path), i))) = heap.pop() {
+        if let Some(entry) = read_run_entry(&mut readers[i])? {
+            heap.push(Reverse((entry, i)));
+        }
+    }
+
+    for run_file in &run_files {
+        std::fs::remove_file(run_file)?;
+    }
+
+    Ok(run_files.len())
+}
+
+/// Finds the `k` newest files of `dir` in one pass, with a min-heap of `k`
+/// entries: `O(n log k)` instead of `O(n log n)` for a full sort.
+/// Returns the number of scanned entries and the `k` newest files, newest first.
+fn read_dir_topk(dir: &str, k: usize) -> std::io::Result<(usize, Vec<PathBuf>)> {
+    let mut heap: BinaryHeap<Reverse<(u128, PathBuf)>> = BinaryHeap::with_capacity(k + 1);
+    let mut count = 0;
+
+    for entry in std::fs::read_dir(dir)? {
+        count += 1;
+        let path = entry?.path();
+
+        let Some(duration_nano) = modified_nanos(&path) else {
+            continue;
+        };
+
+        heap.push(Reverse((duration_nano, path)));
+        if heap.len() > k {
+            heap.pop();
+        }
+    }
+
+    let newest = heap
+        .into_sorted_vec()
+        .into_iter()
+        .map(|Reverse((_duration_nano, path))| path)
+        .collect();
+
+    Ok((count, newest))
+}
+
+/// Reads `max` entries of `dir`, returned sorted by date
+fn sorted_entries(dir: &str, max: usize) -> std::io::Result<Vec<(u128, PathBuf)>> {
+    let mut entries = Vec::with_capacity(max);
+    let mut count = 0;
+
+    while count < max {
+        for entry in std::fs::read_dir(dir)? {
+            count += 1;
+            let path = entry?.path();
+
+            if let Some(duration_nano) = modified_nanos(&path) {
+                entries.push((duration_nano, path));
+            }
+
+            if count == max {
+                break;
+            }
+        }
+    }
+
+    entries.sort_unstable_by_key(|(duration_nano, _)| *duration_nano);
+
+    Ok(entries)
+}
+
+/// Part `i` of `max` items shared between `parts` parts, the first
+/// `max % parts` parts getting one more item
+fn share(max: usize, parts: usize, i: usize) -> usize {
+    max / parts + usize::from(i < max % parts)
+}
+
+/// Scans each directory of `dirs` in its own thread, `max` entries being shared
+/// between them, then merges the sorted scans with a k-way merge.
+/// Returns the number of merged entries.
+fn read_dir_sorted_merge(dirs: &[&str], max: usize) -> std::io::Result<usize> {
+    if dirs.is_empty() {
+        return Ok(0);
+    }
+
+    let scans = std::thread::scope(|s| {
+        let handles: Vec<_> = dirs
+            .iter()
+            .enumerate()
+            .map(|(i, dir)| s.spawn(move || sorted_entries(dir, share(max, dirs.len(), i))))
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .collect::<std::io::Result<Vec<_>>>()
+    })?;
+
+    let mut scans: Vec<_> = scans.into_iter().map(|scan| scan.into_iter()).collect();
+
+    // min-heap of the next entry of each scan, with the index of its scan
+    let mut heap = BinaryHeap::new();
+    for (i, scan) in scans.iter_mut().enumerate() {
+        if let Some(entry) = scan.next() {
+            heap.push(Reverse((entry, i)));
+        }
+    }
+
+    let mut merged = 0;
+    while let Some(Reverse(((_duration_nano, _path), i))) = heap.pop() {
+        merged += 1;
+
+        if let Some(entry) = scans[i].next() {
+            heap.push(Reverse((entry, i)));
+        }
+    }
+
+    Ok(merged)
+}
+
+/// Incremental version of the sorted scan for directories read repeatedly: the
+/// sorted list of the previous call is loaded from `cache_file`, only the entries
+/// not already in it are sorted and merged, the entries not found by the scan
+/// are removed, then the list is saved again.
+/// Returns the number of new entries.
+fn read_dir_sorted_persisted(dir: &str, max: usize, cache_file: &Path) -> std::io::Result<usize> {
+    let mut sorted: Vec<(u128, PathBuf)> = match std::fs::read(cache_file) {
+        Ok(bytes) => bincode::deserialize(&bytes).map_err(std::io::Error::other)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+
+    let known: HashSet<PathBuf> = sorted.iter().map(|(_, path)| path.clone()).collect();
+    let mut seen = HashSet::new();
+    let mut new_entries = Vec::new();
+    let mut count = 0;
+
+    while count < max {
+        for entry in std::fs::read_dir(dir)? {
+            count += 1;
+            let path = entry?.path();
+
+            if known.contains(&path) {
+                seen.insert(path);
+            } else if let Some(duration_nano) = modified_nanos(&path) {
+                new_entries.push((duration_nano, path));
+            }
+
+            if count == max {
+                break;
+            }
+        }
+    }
+
+    let new_count = new_entries.len();
+
+    // deleted files, or beyond the `max` entries read this time
+    sorted.retain(|(_, path)| seen.contains(path));
+    new_entries.sort_unstable_by_key(|(duration_nano, _)| *duration_nano);
+    sorted.append(&mut new_entries);
+    // the stable sort merges the two already sorted runs in linear time
+    sorted.sort_by_key(|(duration_nano, _)| *duration_nano);
+
+    let bytes = bincode::serialize(&sorted).map_err(std::io::Error::other)?;
+    std::fs::write(cache_file, bytes)?;
+
+    Ok(new_count)
+}
+
+/// Size, in bytes, of the name of a record of the index of `read_dir_sorted_mmap`
+const INDEX_NAME_LEN: usize = 256;
+
+/// Size, in bytes, of a record of the index: mtime, inode, name length, name
+const INDEX_RECORD_LEN: usize = 16 + 8 + 2 + INDEX_NAME_LEN;
+
+/// Writes the index read by `read_dir_sorted_mmap`: one fixed-size record per
+/// entry of the `max` first entries of `dir`, sorted by mtime then inode.
+/// Names longer than `INDEX_NAME_LEN` are truncated, entries whose metadata
+/// can't be read are skipped.
+/// Returns the number of records.
+fn write_sorted_index(dir: &str, max: usize, index_file: &Path) -> std::io::Result<usize> {
+    let mut records = Vec::with_capacity(max);
+
+    for entry in std::fs::read_dir(dir)?.take(max) {
+        let entry = entry?;
+        let metadata = match metadata(&entry.path()) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                println!("Can't get metadata for file {:?}: {e}", entry.path());
+                continue;
+            }
+        };
+        let (mtime, ino) = index_entry_key(&metadata);
+        records.push((mtime, ino, entry.file_name()));
+    }
+
+    records.sort_unstable();
+
+    let mut writer = BufWriter::new(File::create(index_file)?);
+    for (mtime, ino, name) in &records {
+        let name = name.as_bytes();
+        let name = &name[..name.len().min(INDEX_NAME_LEN)];
+        let mut padded = [0; INDEX_NAME_LEN];
+        padded[..name.len()].copy_from_slice(name);
+
+        writer.write_all(&mtime.to_le_bytes())?;
+        writer.write_all(&ino.to_le_bytes())?;
+        writer.write_all(&(name.len() as u16).to_le_bytes())?;
+        writer.write_all(&padded)?;
+    }
+    writer.flush()?;
+
+    Ok(records.len())
+}
+
+/// Sort key of an entry in the index: its mtime in nanoseconds, negative
+/// before the epoch, and its inode
+fn index_entry_key(metadata: &std::fs::Metadata) -> (i128, u64) {
+    let mtime = metadata.mtime() as i128 * 1_000_000_000 + metadata.mtime_nsec() as i128;
+    (mtime, metadata.ino())
+}
+
+/// Mtime and inode of a record of the index, its sort key
+fn index_key(record: &[u8]) -> (i128, u64) {
+    let mtime = i128::from_le_bytes(record[0..16].try_into().unwrap());
+    let ino = u64::from_le_bytes(record[16..24].try_into().unwrap());
+    (mtime, ino)
+}
+
+/// Name of a record of the index
+fn index_name(record: &[u8]) -> &[u8] {
+    let len = u16::from_le_bytes([record[24], record[25]]) as usize;
+    &record[26..26 + len]
+}
+
+/// Alternative to rebuilding the sorted map on each scan: the sorted records
+/// written by `write_sorted_index` are mapped in memory, and each of the `max`
+/// first entries of `dir` is binary searched in them. Only the entries not
+/// found are new, they are kept with their insertion point in the index.
+/// Returns the number of new entries.
+fn read_dir_sorted_mmap(dir: &str, max: usize, index_file: &Path) -> std::io::Result<usize> {
+    // the index is only written by `write_sorted_index`, before the scans
+    let mmap = unsafe { Mmap::map(&File::open(index_file)?)? };
+    let index = &mmap[..];
+    let records = index.len() / INDEX_RECORD_LEN;
+    let record = |i: usize| &index[i * INDEX_RECORD_LEN..(i + 1) * INDEX_RECORD_LEN];
+
+    let mut new_entries = Vec::new();
+
+    for entry in std::fs::read_dir(dir)?.take(max) {
+        let entry = entry?;
+        let metadata = match metadata(&entry.path()) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                println!("Can't get metadata for file {:?}: {e}", entry.path());
+                continue;
+            }
+        };
+        let key = index_entry_key(&metadata);
+
+        // first record not lower than the key
+        let (mut low, mut high) = (0, records);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if index_key(record(middle)) < key {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+
+        // hard links share their inode and mtime: all the records of the key
+        // are compared
+        let name = entry.file_name();
+        let name = name.as_bytes();
+        let name = &name[..name.len().min(INDEX_NAME_LEN)];
+        let indexed = (low..records)
+            .map(record)
+            .take_while(|record| index_key(record) == key)
+            .any(|record| index_name(record) == name);
+        if !indexed {
+            new_entries.push((low, entry.path()));
+        }
+    }
+
+    new_entries.sort_unstable();
+
+    Ok(new_entries.len())
+}
+
+/// Splits `items` in at most `threads` partitions of the same size, one per
+/// thread. Fails without thread.
+fn partitions<T>(items: &[T], threads: usize) -> std::io::Result<std::slice::Chunks<'_, T>> {
+    if threads == 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "at least one thread is needed",
+        ));
+    }
+
+    // chunks panics on a size of 0, without items
+    Ok(items.chunks(items.len().div_ceil(threads).max(1)))
+}
+
+/// Collects `max` entries, then shares them between `threads` threads all
+/// counting the processed entries in the same atomic counter.
+/// Returns the value of the counter.
+fn read_dir_atomic_concurrent(dir: &str, max: usize, threads: usize) -> std::io::Result<u64> {
+    let mut entries = Vec::with_capacity(max);
+
+    while entries.len() < max {
+        for entry in std::fs::read_dir(dir)? {
+            entries.push(entry?);
+
+            if entries.len() == max {
+                break;
+            }
+        }
+    }
+
+    let count = AtomicU64::new(0);
+    let partitions = partitions(&entries, threads)?;
+
+    std::thread::scope(|s| {
+        for partition in partitions {
+            let count = &count;
+            s.spawn(move || {
+                for entry in partition {
+                    let _path = entry.path();
+                    count.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+        }
+    });
+
+    Ok(count.into_inner())
+}
+
+/// Same as `read_dir_sorted` with the entries collected first, then shared
+/// between `threads` threads fetching their mtime and inserting them in one
+/// map behind a mutex, to compare with the partition then merge approach of
+/// `read_dir_sorted_merge`.
+fn read_dir_concurrent_sorted(
+    dir: &str,
+    max: usize,
+    threads: usize,
+) -> std::io::Result<BTreeMap<u128, VecDeque<PathBuf>>> {
+    let mut entries = Vec::with_capacity(max);
+
+    while entries.len() < max {
+        for entry in std::fs::read_dir(dir)? {
+            entries.push(entry?.path());
+
+            if entries.len() == max {
+                break;
+            }
+        }
+    }
+
+    let ordered_files: Arc<Mutex<BTreeMap<u128, VecDeque<PathBuf>>>> = Arc::default();
+    let partitions = partitions(&entries, threads)?;
+
+    std::thread::scope(|s| {
+        for partition in partitions {
+            let ordered_files = Arc::clone(&ordered_files);
+            s.spawn(move || {
+                for path in partition {
+                    let Some(duration_nano) = modified_nanos(path) else {
+                        continue;
+                    };
+
+                    ordered_files
+                        .lock()
+                        .unwrap()
+                        .entry(duration_nano)
+                        .or_default()
+                        .push_front(path.clone());
+                }
+            });
+        }
+    });
+
+    let ordered_files = Arc::into_inner(ordered_files).expect("threads are joined");
+    Ok(ordered_files.into_inner().unwrap())
+}
+
+/// Returns true if the kernel supports NUMA memory policies
+#[cfg(target_os = "linux")]
+fn numa_available() -> bool {
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_get_mempolicy,
+            std::ptr::null_mut::<libc::c_int>(),
+            std::ptr::null_mut::<libc::c_ulong>(),
+            0,
+            std::ptr::null_mut::<libc::c_void>(),
+            0,
+        )
+    };
+
+    ret == 0 && Path::new("/sys/devices/system/node/node0").exists()
+}
+
+/// Same as `read_dir_sorted`, in a thread pinned to its current CPU and whose
+/// memory is allocated on the local NUMA node, with `set_mempolicy(MPOL_LOCAL)`.
+#[cfg(target_os = "linux")]
+fn read_dir_numa_sorted(dir: &str, max: usize) -> std::io::Result<()> {
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            // pin the thread so the local node stays the same during the scan
+            let cpu = unsafe { libc::sched_getcpu() };
+            if cpu >= 0 {
+                let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+                unsafe { libc::CPU_SET(cpu as usize, &mut set) };
+                let ret = unsafe { libc::sched_setaffinity(0, std::mem::size_of_val(&set), &set) };
+                if ret != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+
+            let ret = unsafe {
+                libc::syscall(
+                    libc::SYS_set_mempolicy,
+                    libc::MPOL_LOCAL,
+                    std::ptr::null::<libc::c_ulong>(),
+                    0,
+                )
+            };
+            if ret != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+
+            read_dir_sorted(dir, max).map(|_ordered_files| ())
+        })
+        .join()
+        .unwrap()
+    })
+}
+
+/// Takes a non-blocking shared `flock` on `file`, released when it is closed.
+/// Returns false if the file is already locked by someone else.
+fn try_lock_shared(file: &File) -> std::io::Result<bool> {
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_SH | libc::LOCK_NB) } != 0 {
+        let e = std::io::Error::last_os_error();
+        if e.raw_os_error() == Some(libc::EWOULDBLOCK) {
+            return Ok(false);
+        }
+        return Err(e);
+    }
+
+    Ok(true)
+}
+
+/// Takes a non-blocking shared `flock` on each file before counting it, entries
+/// already locked by someone else are skipped, but count in the `max` entries.
+/// Returns the number of skipped entries.
+fn read_dir_with_lock(dir: &str, max: usize) -> std::io::Result<usize> {
+    let mut count = 0;
+    let mut skipped = 0;
+
+    while count < max {
+        for entry in std::fs::read_dir(dir)? {
+            if count == max {
+                break;
+            }
+            count += 1;
+
+            let path = entry?.path();
+            let file = File::open(&path)?;
+
+            if !try_lock_shared(&file)? {
+                skipped += 1;
+            }
+        }
+    }
+
+    Ok(skipped)
+}
+
+/// How `read_dir_with_lock_retry` waits for a file locked by someone else
+struct LockRetryPolicy {
+    max_retries: u32,
+    /// wait before the first retry, doubled at each retry
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+/// Retry policy of the `read_dir_with_lock_retry` benchmark: at most 15.5ms
+/// of wait per file
+const LOCK_RETRY_POLICY: LockRetryPolicy = LockRetryPolicy {
+    max_retries: 5,
+    initial_backoff: Duration::from_micros(500),
+    max_backoff: Duration::from_millis(8),
+};
+
+/// Same as `read_dir_with_lock`, retrying with an exponential backoff to lock
+/// the files locked by someone else, which are only skipped once `policy` gives
+/// up, after at most `max_retries` retries per file.
+/// Returns the paths of the skipped entries.
+fn read_dir_with_lock_retry(
+    dir: &str,
+    max: usize,
+    policy: &LockRetryPolicy,
+) -> std::io::Result<Vec<PathBuf>> {
+    let mut count = 0;
+    let mut skipped = Vec::new();
+
+    while count < max {
+        for entry in std::fs::read_dir(dir)? {
+            if count == max {
+                break;
+            }
+            count += 1;
+
+            let path = entry?.path();
+            let file = File::open(&path)?;
+
+            let mut locked = try_lock_shared(&file)?;
+            let mut backoff = policy.initial_backoff;
+            for _ in 0..policy.max_retries {
+                if locked {
+                    break;
+                }
+
+                std::thread::sleep(backoff);
+                backoff = (backoff * 2).min(policy.max_backoff);
+                locked = try_lock_shared(&file)?;
+            }
+
+            if !locked {
+                skipped.push(path);
+            }
+        }
+    }
+
+    Ok(skipped)
+}
+
+/// Reads entries with `readdir` on a directory fd advised with
+/// `POSIX_FADV_SEQUENTIAL`, to check if it triggers a prefetch of directory blocks.
+#[cfg(target_os = "linux")]
+fn read_dir_with_fadvise(dir: &str, max: usize) -> std::io::Result<()> {
+    let c_dir = CString::new(dir)?;
+    let fd = unsafe { libc::open(c_dir.as_ptr(), libc::O_RDONLY | libc::O_DIRECTORY) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    // posix_fadvise returns the error instead of setting errno
+    let ret = unsafe { libc::posix_fadvise(fd, 0, 0, libc::POSIX_FADV_SEQUENTIAL) };
+    if ret != 0 {
+        unsafe { libc::close(fd) };
+        return Err(std::io::Error::from_raw_os_error(ret));
+    }
+
+    // the fd is owned by the stream from now on, and closed by closedir
+    let stream = unsafe { libc::fdopendir(fd) };
+    if stream.is_null() {
+        let e = std::io::Error::last_os_error();
+        unsafe { libc::close(fd) };
+        return Err(e);
+    }
+
+    let mut count = 0;
+
+    loop {
+        loop {
+            let entry = unsafe { libc::readdir(stream) };
+            if entry.is_null() {
+                break;
+            }
+
+            let name = unsafe { CStr::from_ptr((*entry).d_name.as_ptr()) };
+            let name = OsStr::from_bytes(name.to_bytes());
+            if name == "." || name == ".." {
+                continue;
+            }
+
+            count += 1;
+            let _path = Path::new(dir).join(name);
+
+            if count == max {
+                break;
+            }
+        }
+
+        if count == max {
+            break;
+        }
+
+        unsafe { libc::rewinddir(stream) };
+    }
+
+    unsafe { libc::closedir(stream) };
+
+    Ok(())
+}
+
+/// Number of scans of the same directory by the `read_dir_fd_cache` benchmarks
+const DIR_FD_CACHE_SCANS: usize = 10;
+
+/// Directory kept open between scans: each scan starts again from the first
+/// entry with `rewinddir` instead of opening and closing the directory.
+/// The fd is owned by the stream and closed with it.
+struct DirFdCache {
+    stream: *mut libc::DIR,
+    dir: PathBuf,
+}
+
+impl DirFdCache {
+    fn open(dir: &str) -> std::io::Result<Self> {
+        let c_dir = CString::new(dir)?;
+        let stream = unsafe { libc::opendir(c_dir.as_ptr()) };
+        if stream.is_null() {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            stream,
+            dir: PathBuf::from(dir),
+        })
+    }
+
+    /// Reads the entries of the directory from the first one, up to `max`.
+    /// Returns the number of entries read.
+    fn scan(&mut self, max: usize) -> usize {
+        unsafe { libc::rewinddir(self.stream) };
+        let mut count = 0;
+
+        while count < max {
+            let entry = unsafe { libc::readdir(self.stream) };
+            if entry.is_null() {
+                break;
+            }
+
+            let name = unsafe { CStr::from_ptr((*entry).d_name.as_ptr()) };
+            let name = OsStr::from_bytes(name.to_bytes());
+            if name == "." || name == ".." {
+                continue;
+            }
+
+            count += 1;
+            let _path = self.dir.join(name);
+        }
+
+        count
+    }
+}
+
+impl Drop for DirFdCache {
+    fn drop(&mut self) {
+        unsafe { libc::closedir(self.stream) };
+    }
+}
+
+/// Scans `dir` `scans` times, up to `max` entries each, with the same
+/// `DirFdCache`. Returns the number of entries read.
+fn read_dir_fd_cache(dir: &str, max: usize, scans: usize) -> std::io::Result<usize> {
+    let mut cache = DirFdCache::open(dir)?;
+
+    Ok((0..scans).map(|_| cache.scan(max)).sum())
+}
+
+/// Same as `read_dir_fd_cache`, opening the directory again for each scan
+fn read_dir_fd_uncached(dir: &str, max: usize, scans: usize) -> std::io::Result<usize> {
+    let mut count = 0;
+
+    for _ in 0..scans {
+        for entry in std::fs::read_dir(dir)?.take(max) {
+            let _path = entry?.path();
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+/// Directory opened with `open(2)` and read with the `getdents64` syscall
+#[cfg(target_os = "linux")]
+struct RawDir {
+    fd: OwnedFd,
+}
+
+#[cfg(target_os = "linux")]
+impl RawDir {
+    fn open(dir: &str, flags: libc::c_int) -> std::io::Result<Self> {
+        let c_dir = CString::new(dir)?;
+        let fd = unsafe { libc::open(c_dir.as_ptr(), flags) };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        })
+    }
+
+    /// Calls `f` with the name of each entry, `.` and `..` excluded, from the
+    /// current position up to the end of the directory or until `f` returns false
+    /// (the position is then undefined until `rewind`).
+    fn for_each_name(
+        &self,
+        mut f: impl FnMut(&CStr) -> std::io::Result<bool>,
+    ) -> std::io::Result<()> {
+        let mut buffer = [0u8; 32 * 1024];
+
+        loop {
+            let ret = unsafe {
+                libc::syscall(
+                    libc::SYS_getdents64,
+                    self.fd.as_raw_fd(),
+                    buffer.as_mut_ptr(),
+                    buffer.len(),
+                )
+            };
+            if ret < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            if ret == 0 {
+                return Ok(());
+            }
+
+            // struct linux_dirent64: d_ino (u64), d_off (i64), d_reclen (u16), d_type (u8), d_name
+            let mut offset = 0;
+            while offset < ret as usize {
+                let record = &buffer[offset..];
+                let reclen = u16::from_ne_bytes([record[16], record[17]]) as usize;
+                let name = CStr::from_bytes_until_nul(&record[19..reclen])
+                    .expect("d_name is nul terminated");
+                offset += reclen;
+
+                if name == c"." || name == c".." {
+                    continue;
+                }
+
+                if !f(name)? {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Moves back to the first entry of the directory
+    fn rewind(&self) -> std::io::Result<()> {
+        if unsafe { libc::lseek(self.fd.as_raw_fd(), 0, libc::SEEK_SET) } < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+}
+
+/// Flags of the directory fd of `read_dir_largefile`: `O_LARGEFILE` is only
+/// needed on 32-bit targets, it is implied on 64-bit ones
+#[cfg(target_os = "linux")]
+const LARGEFILE_DIR_FLAGS: libc::c_int = {
+    let flags = libc::O_RDONLY | libc::O_DIRECTORY;
+    #[cfg(target_pointer_width = "32")]
+    let flags = flags | libc::O_LARGEFILE;
+    flags
+};
+
+/// Reads the entry names with `getdents64` on a directory fd opened with
+/// `O_LARGEFILE`, for very large directories on 32-bit targets.
+#[cfg(target_os = "linux")]
+fn read_dir_largefile(dir: &str, max: usize) -> std::io::Result<()> {
+    let raw_dir = RawDir::open(dir, LARGEFILE_DIR_FLAGS)?;
+    let mut count = 0;
+
+    loop {
+        raw_dir.for_each_name(|_name| {
+            count += 1;
+            Ok(count < max)
+        })?;
+
+        if count == max {
+            break;
+        }
+
+        raw_dir.rewind()?;
+    }
+
+    Ok(())
+}
+
+/// Opens each file with its absolute path
+fn read_dir_with_open(dir: &str, max: usize) -> std::io::Result<()> {
+    let mut count = 0;
+
+    loop {
+        for entry in std::fs::read_dir(dir)? {
+            count += 1;
+            let _file = File::open(entry?.path())?;
+
+            if count == max {
+                break;
+            }
+        }
+
+        if count == max {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Opens each file with `openat` relative to the directory fd, using the names
+/// returned by `getdents64`: no path concatenation nor lookup from the root.
+#[cfg(target_os = "linux")]
+fn read_dir_with_openat(dir: &str, max: usize) -> std::io::Result<()> {
+    let raw_dir = RawDir::open(dir, libc::O_RDONLY | libc::O_DIRECTORY)?;
+    let mut count = 0;
+
+    loop {
+        raw_dir.for_each_name(|name| {
+            let fd = unsafe { libc::openat(raw_dir.fd.as_raw_fd(), name.as_ptr(), libc::O_RDONLY) };
+            if fd < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            unsafe { libc::close(fd) };
+
+            count += 1;
+            Ok(count < max)
+        })?;
+
+        if count == max {
+            break;
+        }
+
+        raw_dir.rewind()?;
+    }
+
+    Ok(())
+}
+
+/// Fetches the metadata of each file with `fstatat(2)` relative to the directory
+/// fd. Not with `std::fs::metadata`: it calls `statx` itself on Linux.
+#[cfg(target_os = "linux")]
+fn read_dir_with_metadata(dir: &str, max: usize) -> std::io::Result<()> {
+    let raw_dir = RawDir::open(dir, libc::O_RDONLY | libc::O_DIRECTORY)?;
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    let mut count = 0;
+
+    loop {
+        raw_dir.for_each_name(|name| {
+            if unsafe { libc::fstatat(raw_dir.fd.as_raw_fd(), name.as_ptr(), &mut stat, 0) } != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+
+            count += 1;
+            Ok(count < max)
+        })?;
+
+        if count == max {
+            break;
+        }
+
+        raw_dir.rewind()?;
+    }
+
+    Ok(())
+}
+
+/// Same as `read_dir_with_metadata` with `statx(2)`, only asking for the mtime
+/// and the inode number.
+#[cfg(target_os = "linux")]
+fn read_dir_with_statx(dir: &str, max: usize) -> std::io::Result<()> {
+    let raw_dir = RawDir::open(dir, libc::O_RDONLY | libc::O_DIRECTORY)?;
+    let mut statx: libc::statx = unsafe { std::mem::zeroed() };
+    let mut count = 0;
+
+    loop {
+        raw_dir.for_each_name(|name| {
+            let ret = unsafe {
+                libc::statx(
+                    raw_dir.fd.as_raw_fd(),
+                    name.as_ptr(),
+                    libc::AT_STATX_SYNC_AS_STAT,
+                    libc::STATX_MTIME | libc::STATX_INO,
+                    &mut statx,
+                )
+            };
+            if ret != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+
+            count += 1;
+            Ok(count < max)
+        })?;
+
+        if count == max {
+            break;
+        }
+
+        raw_dir.rewind()?;
+    }
+
+    Ok(())
+}
+
+/// Compares the whole content of `dir` to the paths of a `previous` scan, the
+/// polling alternative to inotify that also works on NFS.
+/// Returns the added and the removed paths.
+fn diff_dir(
+    dir: &str,
+    previous: &HashSet<PathBuf>,
+) -> std::io::Result<(HashSet<PathBuf>, HashSet<PathBuf>)> {
+    let mut current = HashSet::with_capacity(previous.len());
+    for entry in std::fs::read_dir(dir)? {
+        current.insert(entry?.path());
+    }
+
+    let added = current.difference(previous).cloned().collect();
+    let removed = previous.difference(&current).cloned().collect();
+
+    Ok((added, removed))
+}
+
+/// Reads the paths of `max` entries of `dir`, reading it again if needed
+fn collect_paths(dir: &str, max: usize) -> std::io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::with_capacity(max);
+
+    while paths.len() < max {
+        for entry in std::fs::read_dir(dir)? {
+            paths.push(entry?.path());
+
+            if paths.len() == max {
+                break;
+            }
+        }
+    }
+
+    Ok(paths)
+}
+
+/// Collects `max` paths, then fetches their metadata one after the other.
+/// Entries whose metadata can't be read are skipped.
+fn read_dir_collected_metadata(dir: &str, max: usize) -> std::io::Result<()> {
+    for path in collect_paths(dir, max)? {
+        if let Err(e) = metadata(&path) {
+            println!("Can't get metadata for file {path:?}: {e}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Same as `read_dir_collected_metadata`, the metadata being fetched in
+/// parallel by the rayon thread pool
+fn read_dir_rayon_metadata(dir: &str, max: usize) -> std::io::Result<()> {
+    collect_paths(dir, max)?.par_iter().for_each(|path| {
+        if let Err(e) = metadata(path) {
+            println!("Can't get metadata for file {path:?}: {e}");
+        }
+    });
+
+    Ok(())
+}
+
+/// Returns true if io_uring can be used: it can be disabled by the kernel, a
+/// sysctl or a seccomp filter of the container, `io_uring_setup` failing with
+/// `ENOSYS` or `EPERM`
+#[cfg(target_os = "linux")]
+fn io_uring_available() -> bool {
+    IoUring::new(1).is_ok()
+}
+
+/// Number of `statx` submitted at once by `read_dir_with_io_uring`
+#[cfg(target_os = "linux")]
+const IO_URING_ENTRIES: usize = 256;
+
+/// Same as `read_dir_collected_metadata`, the mtime of the files being fetched
+/// by batches of `IO_URING_ENTRIES` `IORING_OP_STATX` operations, one syscall
+/// per batch. Returns the newest mtime, in seconds.
+#[cfg(target_os = "linux")]
+fn read_dir_with_io_uring(dir: &str, max: usize) -> std::io::Result<i64> {
+    let paths = collect_paths(dir, max)?
+        .into_iter()
+        .map(|path| CString::new(path.into_os_string().into_vec()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut ring = IoUring::new(IO_URING_ENTRIES as u32)?;
+    let mut statx_buffers: Vec<libc::statx> = vec![unsafe { std::mem::zeroed() }; IO_URING_ENTRIES];
+    let mut newest = i64::MIN;
+
+    for batch in paths.chunks(IO_URING_ENTRIES) {
+        for (i, (path, statx)) in batch.iter().zip(&mut statx_buffers).enumerate() {
+            let entry = opcode::Statx::new(
+                types::Fd(libc::AT_FDCWD),
+                path.as_ptr(),
+                statx as *mut libc::statx as *mut types::statx,
+            )
+            .mask(libc::STATX_MTIME)
+            .build()
+            .user_data(i as u64);
+
+            // the path and the buffer outlive the operation, completed below
+            unsafe { ring.submission().push(&entry) }.expect("batch fits in the ring");
+        }
+
+        ring.submit_and_wait(batch.len())?;
+
+        for completion in ring.completion() {
+            if completion.result() < 0 {
+                return Err(std::io::Error::from_raw_os_error(-completion.result()));
+            }
+
+            let statx = &statx_buffers[completion.user_data() as usize];
+            newest = newest.max(statx.stx_mtime.tv_sec);
+        }
+    }
+
+    Ok(newest)
+}
+
+/// Reads the content of each file into user space to compute a FNV-1a hash.
+/// Returns the combination of all the hashes.
+fn read_dir_with_checksum(dir: &str, max: usize) -> std::io::Result<u64> {
+    let mut checksum = 0;
+    let mut content = Vec::new();
+    let mut count = 0;
+
+    loop {
+        for entry in std::fs::read_dir(dir)? {
+            count += 1;
+
+            content.clear();
+            File::open(entry?.path())?.read_to_end(&mut content)?;
+
+            let hash = content.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+                (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+            });
+            checksum ^= hash;
+
+            if count == max {
+                break;
+            }
+        }
+
+        if count == max {
+            break;
+        }
+    }
+
+    Ok(checksum)
+}
+
+/// Reads the POSIX access ACL of each file, like `acl_get_file` does, from the
+/// `system.posix_acl_access` extended attribute. A file without extended ACL has
+/// the 3 entries of its mode (owner, group, other).
+/// Returns the total number of ACL entries.
+#[cfg(target_os = "linux")]
+fn read_dir_with_acl(dir: &str, max: usize) -> std::io::Result<usize> {
+    // posix_acl_xattr_header (u32 version) followed by posix_acl_xattr_entry
+    // (u16 tag, u16 perm, u32 id), see linux/posix_acl_xattr.h
+    const HEADER_SIZE: usize = 4;
+    const ENTRY_SIZE: usize = 8;
+    const MODE_ENTRIES: usize = 3;
+
+    let mut buffer = [0u8; 4096];
+    let mut acl_entries = 0;
+    let mut count = 0;
+
+    loop {
+        for entry in std::fs::read_dir(dir)? {
+            count += 1;
+            let path = CString::new(entry?.path().into_os_string().into_vec())?;
+
+            let size = unsafe {
+                libc::getxattr(
+                    path.as_ptr(),
+                    c"system.posix_acl_access".as_ptr(),
+                    buffer.as_mut_ptr() as *mut libc::c_void,
+                    buffer.len(),
+                )
+            };
+
+            if size >= 0 {
+                acl_entries += (size as usize).saturating_sub(HEADER_SIZE) / ENTRY_SIZE;
+            } else {
+                let e = std::io::Error::last_os_error();
+                match e.raw_os_error() {
+                    Some(libc::ENODATA) | Some(libc::EOPNOTSUPP) => acl_entries += MODE_ENTRIES,
+                    _ => return Err(e),
+                }
+            }
+
+            if count == max {
+                break;
+            }
+        }
+
+        if count == max {
+            break;
+        }
+    }
+
+    Ok(acl_entries)
+}
+
+/// Copies the content of each file to `/dev/null` with `sendfile`, without any
+/// copy in user space.
+#[cfg(target_os = "linux")]
+fn read_dir_with_sendfile(dir: &str, max: usize) -> std::io::Result<()> {
+    let null = OpenOptions::new().write(true).open("/dev/null")?;
+    let mut count = 0;
+
+    loop {
+        for entry in std::fs::read_dir(dir)? {
+            count += 1;
+
+            let file = File::open(entry?.path())?;
+            let mut remaining = file.metadata()?.len() as usize;
+            while remaining > 0 {
+                let sent = unsafe {
+                    libc::sendfile64(
+                        null.as_raw_fd(),
+                        file.as_raw_fd(),
+                        std::ptr::null_mut(),
+                        remaining,
+                    )
+                };
+                if sent < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                if sent == 0 {
+                    // file truncated since its size was read
+                    break;
+                }
+                remaining -= sent as usize;
+            }
+
+            if count == max {
+                break;
+            }
+        }
+
+        if count == max {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves `len` bytes from `from` to `to` with `splice`, one of them being a pipe.
+/// Returns the number of bytes moved, 0 at the end of `from`.
+#[cfg(target_os = "linux")]
+fn splice(from: &impl AsRawFd, to: &impl AsRawFd, len: usize) -> std::io::Result<usize> {
+    let moved = unsafe {
+        libc::splice(
+            from.as_raw_fd(),
+            std::ptr::null_mut(),
+            to.as_raw_fd(),
+            std::ptr::null_mut(),
+            len,
+            libc::SPLICE_F_MOVE,
+        )
+    };
+    if moved < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(moved as usize)
+}
+
+/// Same as `read_dir_with_sendfile` with `splice`, the content of each file
+/// going to `/dev/null` through a pipe.
+#[cfg(target_os = "linux")]
+fn read_dir_with_splice(dir: &str, max: usize) -> std::io::Result<()> {
+    let null = OpenOptions::new().write(true).open("/dev/null")?;
+
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let (pipe_read, pipe_write) =
+        unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+
+    let mut count = 0;
+
+    loop {
+        for entry in std::fs::read_dir(dir)? {
+            count += 1;
+
+            let file = File::open(entry?.path())?;
+            let mut remaining = file.metadata()?.len() as usize;
+            while remaining > 0 {
+                // at most the capacity of the pipe, emptied right after
+                let moved = splice(&file, &pipe_write, remaining)?;
+                if moved == 0 {
+                    // file truncated since its size was read
+                    break;
+                }
+
+                let mut in_pipe = moved;
+                while in_pipe > 0 {
+                    in_pipe -= splice(&pipe_read, &null, in_pipe)?;
+                }
+                remaining -= moved;
+            }
+
+            if count == max {
+                break;
+            }
+        }
+
+        if count == max {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the time from adding an inotify watch on `dir` to receiving the
+/// event of a file created in `dir` right after.
+fn measure_inotify_startup_latency(dir: &str) -> Duration {
+    let mut inotify = Inotify::init().expect("Error while initializing inotify instance");
+
+    let start = Instant::now();
+    inotify
+        .watches()
+        .add(dir, WatchMask::CREATE)
+        .expect("Failed to add file watch");
+
+    std::fs::write(format!("{dir}/probe"), "toto").unwrap();
+
+    let mut buffer = [0; 1024];
+    inotify
+        .read_events_blocking(&mut buffer)
+        .expect("Error while reading events");
+
+    start.elapsed()
+}
+
+/// Mean time between opening a file and receiving its `CLOSE_WRITE` event,
+/// for `slots` new files, then for the same files truncated and rewritten
+fn measure_truncate_event_latency(dir: &str, slots: usize) -> (Duration, Duration) {
+    let mut inotify = Inotify::init().expect("Error while initializing inotify instance");
+    inotify
+        .watches()
+        .add(dir, WatchMask::CLOSE_WRITE)
+        .expect("Failed to add file watch");
+
+    let options = CreateOptions {
+        truncate: Some(slots),
+        ..Default::default()
+    };
+    let mut buffer = [0; 1024];
+    let mut latencies = [Duration::ZERO; 2];
+
+    // first pass creates the files, the second one truncates them
+    for latency in &mut latencies {
+        for count in 1..=slots {
+            let path = format!("{dir}/{}", file_name(count, &options));
+            let start = Instant::now();
+            write_file(&path, &options);
+            inotify
+                .read_events_blocking(&mut buffer)
+                .expect("Error while reading events");
+            *latency += start.elapsed();
+        }
+        *latency /= slots as u32;
+    }
+
+    (latencies[0], latencies[1])
+}
+
+/// Overflows of the inotify queue seen by a strategy, the events after an
+/// overflow having been dropped by the kernel
+#[derive(Default)]
+struct OverflowStats {
+    overflows: u32,
+    /// number of events received before the first overflow
+    events_before_first_overflow: Option<u64>,
+}
+
+impl OverflowStats {
+    /// Records an event of type `mask`, `events` events having been received before
+    fn add(&mut self, mask: EventMask, events: u64) {
+        if mask.contains(EventMask::Q_OVERFLOW) {
+            self.overflows += 1;
+            self.events_before_first_overflow.get_or_insert(events);
+        }
+    }
+
+    /// Prints the overflows of the strategy `name` and a `max_queued_events`
+    /// large enough to queue the `files` events of the benchmark
+    fn print(&self, name: &str, files: usize) {
+        let Some(events) = self.events_before_first_overflow else {
+            return;
+        };
+
+        println!(
+            "{name}: inotify queue overflowed {} times, first after {events} events",
+            self.overflows
+        );
+        let max_queued_events = std::fs::read_to_string("/proc/sys/fs/inotify/max_queued_events")
+            .map_or("unknown".to_string(), |value| value.trim().to_string());
+        println!(
+            "    increase /proc/sys/fs/inotify/max_queued_events from {max_queued_events} to {}",
+            files * 3 / 2
+        );
+    }
+}
+
+/// Counts the `ITEM_CREATED` events of the File System Events API of macOS,
+/// up to `max` events.
+#[cfg(target_os = "macos")]
+fn read_dir_fsevents(dir: &str, max: usize) {
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut fsevent = fsevent::FsEvent::new(vec![dir.to_string()]);
+    // events are sent from a thread running the event stream
+    fsevent
+        .observe_async(tx)
+        .expect("Failed to observe directory");
+
+    let mut count = 0;
+
+    for event in &rx {
+        if event.flag.contains(fsevent::StreamFlags::ITEM_CREATED) {
+            count += 1;
+            if count == max {
+                break;
+            }
+        }
+    }
+
+    fsevent.shutdown_observe();
+}
+
+/// Number of files created by the `--inotify-sequence-check` diagnostic
+const INOTIFY_SEQUENCE_FILES: usize = 10_000;
+
+/// Creates the files `seq_1` to `seq_{count}` in `dir` one after the other
+/// and checks that their `CLOSE_WRITE` events arrive in the same order, which
+/// the kernel doesn't guarantee.
+fn read_inotify_sequence_check(dir: &str, count: usize) -> bool {
+    let mut inotify = Inotify::init().expect("Error while initializing inotify instance");
+    inotify
+        .watches()
+        .add(dir, WatchMask::CLOSE_WRITE)
+        .expect("Failed to add file watch");
+
+    let writer_dir = dir.to_string();
+    let writer = std::thread::spawn(move || {
+        for n in 1..=count {
+            std::fs::write(format!("{writer_dir}/seq_{n}"), "toto").unwrap();
+        }
+    });
+
+    let mut buffer = [0; 8096];
+    let mut expected = 1;
+    let mut in_order = true;
+
+    while expected <= count {
+        let events = inotify
+            .read_events_blocking(&mut buffer)
+            .expect("Error while reading events");
+
+        for event in events {
+            let Some(n) = event
+                .name
+                .and_then(|name| name.to_str()?.strip_prefix("seq_")?.parse::<usize>().ok())
+            else {
+                continue;
+            };
+
+            if n != expected {
+                println!("inotify sequence: seq_{n} received instead of seq_{expected}");
+                in_order = false;
+            }
+            expected = n + 1;
+        }
+    }
+
+    writer.join().unwrap();
+    in_order
+}
+
+fn read_inotify(dir: &String, max: usize) -> OverflowStats {
+    let inotify = Inotify::init().expect("Error while initializing inotify instance");
+    inotify
+        .watches()
+        .add(dir, WatchMask::CLOSE_WRITE)
+        .expect("Failed to add file watch");
+
+    read_inotify_events(inotify, max)
+}
+
+/// Reads `max` events with a file name from `inotify`, already watching a
+/// directory
+fn read_inotify_events(mut inotify: Inotify, max: usize) -> OverflowStats {
+    let mut buffer = [0; 8096];
+    let mut overflow = OverflowStats::default();
+    let mut count = 0;
+
+    loop {
+        let events = inotify
+            .read_events_blocking(&mut buffer)
+            .expect("Error while reading events");
+
+        for event in events {
+            overflow.add(event.mask, count as u64);
+
+            // Handle event
+            if let Some(_filename) = event.name {
+                count += 1;
+
+                if count == max {
+                    break;
+                }
+            }
+        }
+
+        if count == max {
+            break;
+        }
+    }
+
+    overflow
+}
+
+/// Runs `read_inotify` and `read_dir` at the same time in two threads, both up
+/// to `max` entries, to measure how much they slow each other down.
+/// Returns the durations of `read_inotify` and of `read_dir`.
+fn read_dir_concurrent_inotify_readdir(dir: &str, max: usize) -> (Duration, Duration) {
+    let dir = dir.to_string();
+
+    std::thread::scope(|s| {
+        let inotify_thread = s.spawn(|| {
+            let start = Instant::now();
+            read_inotify(&dir, max);
+            start.elapsed()
+        });
+        let read_dir_thread = s.spawn(|| {
+            let start = Instant::now();
+            read_dir(&dir, max).unwrap();
+            start.elapsed()
+        });
+
+        (
+            inotify_thread.join().unwrap(),
+            read_dir_thread.join().unwrap(),
+        )
+    })
+}
+
+/// Numbers of inotify instances of `read_inotify_multi_instance`
+const INOTIFY_INSTANCES: [usize; 3] = [1, 2, 4];
+
+/// Runs `read_inotify` in `instances` threads, each with its own inotify
+/// instance watching `dir` and counting `max / instances` events, like
+/// applications with one watcher per thread.
+/// Returns the time each instance took to count its events.
+fn read_inotify_multi_instance(dir: &str, max: usize, instances: usize) -> Vec<Duration> {
+    let per_instance = max / instances;
+    let dir = dir.to_string();
+
+    std::thread::scope(|s| {
+        let handles: Vec<_> = (0..instances)
+            .map(|_| {
+                s.spawn(|| {
+                    let start = Instant::now();
+                    read_inotify(&dir, per_instance);
+                    start.elapsed()
+                })
+            })
+            .collect();
+
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    })
+}
+
+/// Time without event after which `read_inotify_with_idle_timeout` stops
+const INOTIFY_IDLE_TIMEOUT_MS: u64 = 1000;
+
+/// Same as `read_inotify`, stopping early if no event arrives for
+/// `idle_timeout`, like log shippers draining a queue.
+/// Returns the number of events received.
+fn read_inotify_with_idle_timeout(dir: &str, max: usize, idle_timeout: Duration) -> usize {
+    let mut inotify = Inotify::init().expect("Error while initializing inotify instance");
+    inotify
+        .watches()
+        .add(dir, WatchMask::CLOSE_WRITE)
+        .expect("Failed to add file watch");
+
+    let mut buffer = [0; 8096];
+    let mut count = 0;
+
+    while count < max {
+        // read_events_blocking has no timeout: wait for the events with poll
+        let mut pollfd = libc::pollfd {
+            fd: inotify.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout_ms = idle_timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+        match unsafe { libc::poll(&mut pollfd, 1, timeout_ms) } {
+            0 => break,
+            ret if ret < 0 => {
+                let e = std::io::Error::last_os_error();
+                if e.kind() != std::io::ErrorKind::Interrupted {
+                    panic!("Error while polling events: {e}");
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        match inotify.read_events(&mut buffer) {
+            Ok(events) => count = max.min(count + events.count()),
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+            Err(e) => panic!("Error while reading events: {e}"),
+        }
+    }
+
+    count
+}
+
+/// Polling interval of `read_inotify_reconnect` while the directory is missing
+const INOTIFY_RECONNECT_POLL_MS: u64 = 10;
+
+/// Same as `read_inotify`, watching `dir` again once it is recreated after being
+/// deleted: the kernel removes the watch of a deleted directory.
+/// Returns the number of times the watch was added again.
+fn read_inotify_reconnect(dir: &str, max: usize) -> usize {
+    let mask = WatchMask::CLOSE_WRITE | WatchMask::DELETE_SELF;
+    let mut inotify = Inotify::init().expect("Error while initializing inotify instance");
+    inotify
+        .watches()
+        .add(dir, mask)
+        .expect("Failed to add file watch");
+
+    let mut buffer = [0; 8096];
+    let mut reconnects = 0;
+    let mut count = 0;
+
+    loop {
+        let events = inotify
+            .read_events_blocking(&mut buffer)
+            .expect("Error while reading events");
+
+        let mut deleted = false;
+        for event in events {
+            if event.mask.contains(EventMask::DELETE_SELF) {
+                deleted = true;
+                continue;
+            }
+
+            if let Some(_filename) = event.name {
+                count += 1;
+
+                if count == max {
+                    break;
+                }
+            }
+        }
+
+        if count == max {
+            break;
+        }
+
+        if deleted {
+            while !std::fs::metadata(dir).is_ok_and(|metadata| metadata.is_dir()) {
+                std::thread::sleep(Duration::from_millis(INOTIFY_RECONNECT_POLL_MS));
+            }
+
+            inotify
+                .watches()
+                .add(dir, mask)
+                .expect("Failed to add file watch");
+            reconnects += 1;
+        }
+    }
+
+    reconnects
+}
+
+/// Number of events returned by each `read_events_blocking` call
+#[derive(Default)]
+struct BatchStats {
+    batches: usize,
+    events: usize,
+    /// number of batches, by batch size rounded up to a power of two
+    histogram: BTreeMap<usize, usize>,
+}
+
+impl BatchStats {
+    fn add_batch(&mut self, size: usize) {
+        self.batches += 1;
+        self.events += size;
+        *self.histogram.entry(size.next_power_of_two()).or_default() += 1;
+    }
+
+    fn average(&self) -> f64 {
+        if self.batches == 0 {
+            return 0.0;
+        }
+
+        self.events as f64 / self.batches as f64
+    }
+}
+
+/// Same as `read_inotify` with a buffer of `buffer_size` bytes, recording the
+/// number of events returned by each read.
+fn read_inotify_batch(dir: &str, max: usize, buffer_size: usize) -> BatchStats {
+    let mut inotify = Inotify::init().expect("Error while initializing inotify instance");
+    inotify
+        .watches()
+        .add(dir, WatchMask::CLOSE_WRITE)
+        .expect("Failed to add file watch");
+
+    let mut buffer = vec![0; buffer_size];
+    let mut stats = BatchStats::default();
+    let mut count = 0;
+
+    loop {
+        let events = inotify
+            .read_events_blocking(&mut buffer)
+            .expect("Error while reading events");
+
+        let mut batch = 0;
+        for event in events {
+            batch += 1;
+
+            if let Some(_filename) = event.name {
+                count += 1;
+
+                if count == max {
+                    break;
+                }
+            }
+        }
+
+        stats.add_batch(batch);
+
+        if count == max {
+            break;
+        }
+    }
+
+    stats
+}
+
+/// Same as `read_inotify`, accumulating the file names in chunks of `chunk`
+/// events processed at once, like a batch sent to a message queue. Returns the
+/// number of chunks processed.
+fn read_inotify_chunked(dir: &str, total: usize, chunk: usize) -> usize {
+    let mut inotify = Inotify::init().expect("Error while initializing inotify instance");
+    inotify
+        .watches()
+        .add(dir, WatchMask::CLOSE_WRITE)
+        .expect("Failed to add file watch");
+
+    let mut buffer = [0; 8096];
+    let mut pending: Vec<OsString> = Vec::with_capacity(chunk);
+    let mut chunks = 0;
+    let mut count = 0;
+
+    loop {
+        let events = inotify
+            .read_events_blocking(&mut buffer)
+            .expect("Error while reading events");
+
+        for event in events {
+            if let Some(filename) = event.name {
+                pending.push(filename.to_owned());
+                count += 1;
+
+                // processing the chunk, the last one may be incomplete
+                if pending.len() == chunk || count == total {
+                    pending.clear();
+                    chunks += 1;
+                }
+
+                if count == total {
+                    break;
+                }
+            }
+        }
+
+        if count == total {
+            break;
+        }
+    }
+
+    chunks
+}
+
+/// Creates a fanotify notification group, needs `CAP_SYS_ADMIN`
+#[cfg(target_os = "linux")]
+fn fanotify_init() -> std::io::Result<OwnedFd> {
+    let fd = unsafe {
+        libc::fanotify_init(
+            libc::FAN_CLASS_NOTIF | libc::FAN_CLOEXEC,
+            libc::O_RDONLY as libc::c_uint,
+        )
+    };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// Counts the `FAN_CLOSE_WRITE` events of the files of `dir` written by the
+/// process `target_pid` only, up to `max` events.
+#[cfg(target_os = "linux")]
+fn read_fanotify_pid_filter(dir: &str, max: usize, target_pid: i32) -> std::io::Result<()> {
+    let fanotify = fanotify_init()?;
+
+    let c_dir = CString::new(dir)?;
+    let ret = unsafe {
+        libc::fanotify_mark(
+            fanotify.as_raw_fd(),
+            libc::FAN_MARK_ADD,
+            libc::FAN_CLOSE_WRITE | libc::FAN_EVENT_ON_CHILD,
+            libc::AT_FDCWD,
+            c_dir.as_ptr(),
+        )
+    };
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    let mut buffer = [0u8; 8192];
+    let mut count = 0;
+
+    loop {
+        let len = unsafe {
+            libc::read(
+                fanotify.as_raw_fd(),
+                buffer.as_mut_ptr() as *mut libc::c_void,
+                buffer.len(),
+            )
+        };
+        if len < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        let mut offset = 0;
+        while offset + std::mem::size_of::<libc::fanotify_event_metadata>() <= len as usize {
+            let event: libc::fanotify_event_metadata =
+                unsafe { std::ptr::read_unaligned(buffer[offset..].as_ptr() as *const _) };
+            offset += event.event_len as usize;
+
+            // each event comes with an open fd on the file
+            if event.fd >= 0 {
+                unsafe { libc::close(event.fd) };
+            }
+
+            if event.pid == target_pid && count < max {
+                count += 1;
+            }
+        }
+
+        if count == max {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Same as `read_inotify`, only counting events whose file name matches the
+/// wildcard `pattern`. Non UTF-8 names are matched after a lossy conversion.
+fn read_inotify_filter(dir: &str, max: usize, pattern: &str) {
+    let mut inotify = Inotify::init().expect("Error while initializing inotify instance");
+    inotify
+        .watches()
+        .add(dir, WatchMask::CLOSE_WRITE)
+        .expect("Failed to add file watch");
+
+    let pattern = WildMatch::new(pattern);
+    let mut buffer = [0; 8096];
+    let mut count = 0;
+
+    loop {
+        let events = inotify
+            .read_events_blocking(&mut buffer)
+            .expect("Error while reading events");
+
+        for event in events {
+            if let Some(filename) = event.name {
+                if !pattern.matches(&filename.to_string_lossy()) {
+                    continue;
+                }
+
+                count += 1;
+
+                if count == max {
+                    break;
+                }
+            }
+        }
+
+        if count == max {
+            break;
+        }
+    }
+}
+
+/// Same as `read_inotify`, but a file is only counted once no other event was
+/// received for it during `debounce_ms` (the "file is done writing" heuristic).
+fn read_inotify_debounce(dir: &str, max: usize, debounce_ms: u64) {
+    let mut inotify = Inotify::init().expect("Error while initializing inotify instance");
+    inotify
+        .watches()
+        .add(dir, WatchMask::CLOSE_WRITE)
+        .expect("Failed to add file watch");
+
+    let window = Duration::from_millis(debounce_ms);
+    let mut pending: HashMap<OsString, Instant> = HashMap::new();
+    let mut buffer = [0; 8096];
+    let mut count = 0;
+
+    loop {
+        // wake up at least once per window to count the files that became quiet
+        let mut pollfd = libc::pollfd {
+            fd: inotify.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        unsafe { libc::poll(&mut pollfd, 1, debounce_ms as libc::c_int) };
+
+        match inotify.read_events(&mut buffer) {
+            Ok(events) => {
+                for event in events {
+                    if let Some(filename) = event.name {
+                        pending.insert(filename.to_os_string(), Instant::now());
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+            Err(e) => panic!("Error while reading events: {e}"),
+        }
+
+        let now = Instant::now();
+        pending.retain(|_filename, last_event| {
+            if now.duration_since(*last_event) < window {
+                return true;
+            }
+
+            count += 1;
+            false
+        });
+
+        if count >= max {
+            break;
+        }
+    }
+}
+
+/// Same as `read_inotify`, reading all the queued events at once, keeping the
+/// last event of each file name, then processing the files most recently
+/// received first, like log tailers wanting the freshest data first. inotify
+/// doesn't timestamp events: they are timestamped when read.
+/// Returns the number of events dropped as duplicates.
+fn read_inotify_priority(dir: &str, max: usize) -> usize {
+    let mut inotify = Inotify::init().expect("Error while initializing inotify instance");
+    inotify
+        .watches()
+        .add(dir, WatchMask::CLOSE_WRITE)
+        .expect("Failed to add file watch");
+
+    let mut pending: HashMap<OsString, Instant> = HashMap::new();
+    let mut buffer = [0; 65536];
+    let mut duplicates = 0;
+    let mut count = 0;
+
+    while count < max {
+        let events = inotify
+            .read_events_blocking(&mut buffer)
+            .expect("Error while reading events");
+
+        for event in events {
+            if let Some(filename) = event.name {
+                if pending
+                    .insert(filename.to_os_string(), Instant::now())
+                    .is_some()
+                {
+                    duplicates += 1;
+                }
+            }
+        }
+
+        let mut files: Vec<_> = pending.drain().collect();
+        files.sort_unstable_by_key(|(_filename, received)| Reverse(*received));
+
+        for (filename, _received) in files {
+            let _path = Path::new(dir).join(filename);
+
+            count += 1;
+            if count == max {
+                break;
+            }
+        }
+    }
+
+    duplicates
+}
+
+/// Number of count queries of the `read_dir_count_cached` benchmarks
+const COUNT_QUERIES: usize = 10;
+
+/// Number of entries of a directory, counted once with `read_dir` then kept up
+/// to date with its `CREATE` and `DELETE` inotify events
+struct DirCountCache {
+    dir: PathBuf,
+    count: usize,
+    /// number of events applied and of entries scanned again, the work done
+    processed: usize,
+    watcher: Inotify,
+}
+
+impl DirCountCache {
+    fn new(dir: &str) -> std::io::Result<Self> {
+        let watcher = Inotify::init()?;
+        // added before the scan: a file created meanwhile may be counted twice,
+        // but not missed
+        watcher
+            .watches()
+            .add(dir, WatchMask::CREATE | WatchMask::DELETE)?;
+
+        let mut cache = Self {
+            dir: PathBuf::from(dir),
+            count: 0,
+            processed: 0,
+            watcher,
+        };
+        cache.rescan()?;
+
+        Ok(cache)
+    }
+
+    fn rescan(&mut self) -> std::io::Result<()> {
+        self.count = std::fs::read_dir(&self.dir)?.count();
+        self.processed += self.count;
+        Ok(())
+    }
+
+    /// Applies the pending events to the count, without waiting for new ones.
+    /// The directory is scanned again if events were lost.
+    fn update(&mut self) -> std::io::Result<()> {
+        let mut buffer = [0; 65536];
+        let mut overflow = false;
+
+        loop {
+            let events = match self.watcher.read_events(&mut buffer) {
+                Ok(events) => events,
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            };
+
+            for event in events {
+                self.processed += 1;
+                if event.mask.contains(EventMask::Q_OVERFLOW) {
+                    overflow = true;
+                } else if event.mask.contains(EventMask::CREATE) {
+                    self.count += 1;
+                } else if event.mask.contains(EventMask::DELETE) {
+                    self.count = self.count.saturating_sub(1);
+                }
+            }
+        }
+
+        if overflow {
+            self.rescan()?;
+        }
+
+        Ok(())
+    }
+
+    /// Number of entries of the directory, as of the last pending event
+    fn count(&mut self) -> std::io::Result<usize> {
+        self.update()?;
+        Ok(self.count)
+    }
+}
+
+/// Same as `read_inotify`, ignoring the events of the files already in `dir`
+/// before the watch is added, to only process newly arriving files. A file
+/// still being written when `dir` is listed is ignored too.
+/// Returns the number of ignored events.
+fn read_inotify_suppress_initial(dir: &str, max: usize) -> std::io::Result<usize> {
+    let existing = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.file_name()))
+        .collect::<std::io::Result<HashSet<OsString>>>()?;
+
+    let mut inotify = Inotify::init()?;
+    inotify.watches().add(dir, WatchMask::CLOSE_WRITE)?;
+
+    let mut buffer = [0; 8096];
+    let mut ignored = 0;
+    let mut count = 0;
+
+    while count < max {
+        for event in inotify.read_events_blocking(&mut buffer)? {
+            let Some(filename) = event.name else {
+                continue;
+            };
+
+            if existing.contains(filename) {
+                ignored += 1;
+                continue;
+            }
+
+            count += 1;
+            if count == max {
+                break;
+            }
+        }
+    }
+
+    Ok(ignored)
+}
+
+/// Same as `read_inotify`, checking that the name of each event is valid
+/// UTF-8, as code converting it with `String::from_utf8_lossy` assumes.
+/// Returns the number of names that are not.
+fn read_inotify_utf8_check(dir: &str, max: usize) -> usize {
+    let mut inotify = Inotify::init().expect("Error while initializing inotify instance");
+    inotify
+        .watches()
+        .add(dir, WatchMask::CLOSE_WRITE)
+        .expect("Failed to add file watch");
+
+    let mut buffer = [0; 8096];
+    let mut invalid = 0;
+    let mut count = 0;
+
+    while count < max {
+        let events = inotify
+            .read_events_blocking(&mut buffer)
+            .expect("Error while reading events");
+
+        for event in events {
+            let Some(filename) = event.name else {
+                continue;
+            };
+
+            if filename.to_str().is_none() {
+                invalid += 1;
+            }
+
+            count += 1;
+            if count == max {
+                break;
+            }
+        }
+    }
+
+    invalid
+}
+
+/// Counts `IN_MOVED_FROM` and `IN_MOVED_TO` events, up to `max` events. Both
+/// events of a rename within `dir` share the same cookie, a move from or to
+/// another directory only has one of them. Other events, like `IN_IGNORED` or
+/// `IN_Q_OVERFLOW`, have no cookie and are not paired.
+/// Returns the number of renames, of unpaired events, and the overflows.
+fn read_inotify_move(dir: &str, max: usize) -> (usize, usize, OverflowStats) {
+    let mut inotify = Inotify::init().expect("Error while initializing inotify instance");
+    inotify
+        .watches()
+        .add(dir, WatchMask::MOVED_FROM | WatchMask::MOVED_TO)
+        .expect("Failed to add file watch");
+
+    // cookies of the events whose other half wasn't received yet
+    let mut pending = HashSet::new();
+    let mut renames = 0;
+    let mut overflow = OverflowStats::default();
+    let mut buffer = [0; 8096];
+    let mut count = 0;
+
+    loop {
+        let events = inotify
+            .read_events_blocking(&mut buffer)
+            .expect("Error while reading events");
+
+        for event in events {
+            overflow.add(event.mask, count as u64);
+
+            let moved = event
+                .mask
+                .intersects(EventMask::MOVED_FROM | EventMask::MOVED_TO);
+            if moved && event.cookie != 0 && !pending.insert(event.cookie) {
+                pending.remove(&event.cookie);
+                renames += 1;
+            }
+
+            count += 1;
+            if count == max {
+                break;
+            }
+        }
+
+        if count == max {
+            break;
+        }
+    }
+
+    (renames, pending.len(), overflow)
+}
+
+/// Number of inotify events of each type
+#[derive(Default)]
+struct EventBreakdown {
+    counts: HashMap<EventMask, u64>,
+}
+
+impl EventBreakdown {
+    /// Counts each type of `mask`, e.g. twice for `CREATE | ISDIR`
+    fn add(&mut self, mask: EventMask) {
+        for flag in mask.iter() {
+            *self.counts.entry(flag).or_default() += 1;
+        }
+    }
+}
+
+/// One line per event type with its percentage, most frequent first
+impl std::fmt::Display for EventBreakdown {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let total: u64 = self.counts.values().sum();
+
+        let mut counts: Vec<_> = self
+            .counts
+            .iter()
+            .map(|(flag, count)| {
+                let name = flag.iter_names().next().map_or("UNKNOWN", |(name, _)| name);
+                (name, *count)
+            })
+            .collect();
+        counts.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+        for (name, count) in counts {
+            let percent = count as f64 * 100.0 / total as f64;
+            writeln!(f, "    {name:<13} {percent:>5.1}% ({count})")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Counts events of any type of `mask`, up to `max` events.
+fn read_inotify_multi_event(dir: &str, max: usize, mask: WatchMask) -> EventBreakdown {
+    let mut inotify = Inotify::init().expect("Error while initializing inotify instance");
+    inotify
+        .watches()
+        .add(dir, mask)
+        .expect("Failed to add file watch");
+
+    let mut breakdown = EventBreakdown::default();
+    let mut buffer = [0; 8096];
+    let mut count = 0;
+
+    loop {
+        let events = inotify
+            .read_events_blocking(&mut buffer)
+            .expect("Error while reading events");
+
+        for event in events {
+            breakdown.add(event.mask);
+
+            count += 1;
+            if count == max {
+                break;
+            }
+        }
+
+        if count == max {
+            break;
+        }
+    }
+
+    breakdown
+}
+
+fn read_inotify_async(dir: &String, max: usize) -> OverflowStats {
+    let inotify = Inotify::init().expect("Error while initializing inotify instance");
+    inotify
+        .watches()
+        .add(dir, WatchMask::CLOSE_WRITE)
+        .expect("Failed to add file watch");
+
+    let rt = Runtime::new().unwrap();
+
+    rt.block_on(async {
+        // Read events that were added with `Watches::add` above.
+        let mut buffer = [0; 1024];
+
+        // les différents types d'événements pour tokio_select : inotify, signal, timer
+        let mut stream = inotify.into_event_stream(&mut buffer).unwrap();
+
+        let mut overflow = OverflowStats::default();
+        let mut count = 0;
+
+        loop {
+            tokio::select! {
+                event = stream.next() => {
+                    if let Some(Ok(event)) = event {
+                        overflow.add(event.mask, count as u64);
+                    }
+
+                    count += 1;
+                    if count == max {
+                        break;
+                    }
+                },
+            }
+        }
+
+        overflow
+    })
+}
+
+/// Number of directories watched by `read_inotify_async_multi`, each filled by
+/// its own writer thread
+const INOTIFY_MULTI_DIRS: usize = 4;
+
+/// Time after which `read_inotify_async_multi` and `read_inotify_async_per_dir`
+/// are cancelled, in case a writer stops
+const INOTIFY_MULTI_TIMEOUT_MS: u64 = 30_000;
+
+/// Same as `read_inotify_async` with all the directories of `dirs` watched by
+/// one inotify instance, read by one async task, up to `max` events in total.
+/// Returns the number of events of each directory.
+fn read_inotify_async_multi(dirs: &[&str], max: usize) -> Vec<usize> {
+    let inotify = Inotify::init().expect("Error while initializing inotify instance");
+    let watches: Vec<_> = dirs
+        .iter()
+        .map(|dir| {
+            inotify
+                .watches()
+                .add(dir, WatchMask::CLOSE_WRITE)
+                .expect("Failed to add file watch")
+        })
+        .collect();
+
+    let rt = Runtime::new().unwrap();
+
+    rt.block_on(async {
+        let mut buffer = [0; 1024];
+        let mut stream = inotify.into_event_stream(&mut buffer).unwrap();
+        let cancel = tokio::time::sleep(Duration::from_millis(INOTIFY_MULTI_TIMEOUT_MS));
+        tokio::pin!(cancel);
+
+        let mut counts = vec![0; dirs.len()];
+        let mut count = 0;
+
+        while count < max {
+            tokio::select! {
+                event = stream.next() => {
+                    let Some(Ok(event)) = event else {
+                        continue;
+                    };
+
+                    if let Some(i) = watches.iter().position(|wd| *wd == event.wd) {
+                        counts[i] += 1;
+                    }
+                    count += 1;
+                },
+                _ = &mut cancel => {
+                    println!("read_inotify_async_multi: cancelled after {count} events");
+                    break;
+                },
+            }
+        }
+
+        counts
+    })
+}
+
+/// Same as `read_inotify_async_multi` with one async task per directory, each
+/// with its own inotify instance, `max` events being shared between them.
+/// Returns the number of events of each directory.
+fn read_inotify_async_per_dir(dirs: &[&str], max: usize) -> Vec<usize> {
+    let rt = Runtime::new().unwrap();
+
+    rt.block_on(async {
+        let mut tasks = JoinSet::new();
+
+        for (i, dir) in dirs.iter().enumerate() {
+            let inotify = Inotify::init().expect("Error while initializing inotify instance");
+            inotify
+                .watches()
+                .add(dir, WatchMask::CLOSE_WRITE)
+                .expect("Failed to add file watch");
+            let per_dir = share(max, dirs.len(), i);
+
+            tasks.spawn(async move {
+                let mut buffer = [0; 1024];
+                let mut stream = inotify.into_event_stream(&mut buffer).unwrap();
+                let cancel = tokio::time::sleep(Duration::from_millis(INOTIFY_MULTI_TIMEOUT_MS));
+                tokio::pin!(cancel);
+
+                let mut count = 0;
+
+                while count < per_dir {
+                    tokio::select! {
+                        _event = stream.next() => count += 1,
+                        _ = &mut cancel => break,
+                    }
+                }
+
+                (i, count)
+            });
+        }
+
+        let mut counts = vec![0; dirs.len()];
+        while let Some(result) = tasks.join_next().await {
+            let (i, count) = result.unwrap();
+            counts[i] = count;
+        }
+
+        counts
+    })
+}
+
+/// Watches each directory of `watches` with its own mask in one inotify
+/// instance, e.g. `CLOSE_WRITE` for a log directory and `ATTRIB | CLOSE_WRITE`
+/// for a config directory, up to `max` events in total.
+/// Returns the number of events of each directory.
+fn read_inotify_heterogeneous(watches: &[(String, WatchMask)], max: usize) -> Vec<usize> {
+    let mut inotify = Inotify::init().expect("Error while initializing inotify instance");
+    let descriptors: Vec<_> = watches
+        .iter()
+        .map(|(dir, mask)| {
+            inotify
+                .watches()
+                .add(dir, *mask)
+                .expect("Failed to add file watch")
+        })
+        .collect();
+
+    let mut buffer = [0; 8096];
+    let mut counts = vec![0; watches.len()];
+    let mut count = 0;
+
+    while count < max {
+        let events = inotify
+            .read_events_blocking(&mut buffer)
+            .expect("Error while reading events");
+
+        for event in events {
+            if let Some(i) = descriptors.iter().position(|wd| *wd == event.wd) {
+                counts[i] += 1;
+            }
+
+            count += 1;
+            if count == max {
+                break;
+            }
+        }
+    }
+
+    counts
+}
+
+/// Same as `read_inotify_async`, stopping cleanly on SIGINT or SIGTERM.
+/// Returns the number of events received and whether it was interrupted.
+fn read_inotify_graceful(dir: &str, max: usize) -> (usize, bool) {
+    let inotify = Inotify::init().expect("Error while initializing inotify instance");
+    inotify
+        .watches()
+        .add(dir, WatchMask::CLOSE_WRITE)
+        .expect("Failed to add file watch");
+
+    let rt = Runtime::new().unwrap();
+
+    rt.block_on(async {
+        let mut buffer = [0; 1024];
+        let mut stream = inotify.into_event_stream(&mut buffer).unwrap();
+        let mut terminate = tokio::signal::unix::signal(SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler");
+        // created once so a signal received between two polls is not missed
+        let ctrl_c = tokio::signal::ctrl_c();
+        tokio::pin!(ctrl_c);
+
+        let mut count = 0;
+
+        loop {
+            tokio::select! {
+                _event = stream.next() => {
+                    count += 1;
+                    if count == max {
+                        return (count, false);
+                    }
+                },
+                _ = &mut ctrl_c => {
+                    println!("Interrupted after {count} events.");
+                    return (count, true);
+                },
+                _ = terminate.recv() => {
+                    println!("Terminated after {count} events.");
+                    return (count, true);
+                },
+            }
+        }
+    })
+}
+
+/// Async stream over the paths of the entries of a directory, the async
+/// counterpart of `dir_entries`. An error opening the directory is returned as
+/// the only item.
+struct DirStream {
+    state: DirStreamState,
+}
+
+enum DirStreamState {
+    Opening(Pin<Box<dyn Future<Output = std::io::Result<tokio::fs::ReadDir>> + Send>>),
+    Reading(tokio::fs::ReadDir),
+    Done,
+}
+
+impl DirStream {
+    fn new(dir: impl AsRef<Path>) -> Self {
+        let dir = dir.as_ref().to_path_buf();
+
+        Self {
+            state: DirStreamState::Opening(Box::pin(tokio::fs::read_dir(dir))),
+        }
+    }
+}
+
+impl Stream for DirStream {
+    type Item = std::io::Result<PathBuf>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            match &mut this.state {
+                DirStreamState::Opening(open) => match ready!(open.as_mut().poll(cx)) {
+                    Ok(read_dir) => this.state = DirStreamState::Reading(read_dir),
+                    Err(e) => {
+                        this.state = DirStreamState::Done;
+                        return Poll::Ready(Some(Err(e)));
+                    }
+                },
+                DirStreamState::Reading(read_dir) => {
+                    return match ready!(read_dir.poll_next_entry(cx)) {
+                        Ok(Some(entry)) => Poll::Ready(Some(Ok(entry.path()))),
+                        Ok(None) => {
+                            this.state = DirStreamState::Done;
+                            Poll::Ready(None)
+                        }
+                        Err(e) => Poll::Ready(Some(Err(e))),
+                    };
+                }
+                DirStreamState::Done => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+fn read_dir_tokio(dir: &String, max: usize) -> std::io::Result<()> {
+    let rt = Runtime::new().unwrap();
+
+    rt.block_on(async {
+        let mut count = 0;
+
+        loop {
+            let mut stream = DirStream::new(dir);
+
+            loop {
+                tokio::select! {
+                    event = stream.next() => {
+                        match event {
+                            Some(Ok(_path)) => {
+                                count += 1;
+                                if count == max {
+                                    break;
+                                }
+                            }
+                            Some(Err(e)) => return Err(e),
+                            // end of directory, read it again
+                            None => break,
+                        }
+                    },
+                }
+            }
+
+            if count == max {
+                break;
+            }
+        }
+
+        Ok(())
+    })
+}
+
+/// Collects up to `max` entries of one read of `dir` with the `DirStream`
+/// adapters, failing on the first entry in error.
+async fn read_dir_async_collect(dir: &str, max: usize) -> std::io::Result<Vec<PathBuf>> {
+    DirStream::new(dir).take(max).try_collect().await
+}
+
+/// Capacity of the channel between the I/O and the sort tasks of
+/// `read_dir_sorted_async`
+const SORT_CHANNEL_CAPACITY: usize = 1024;
+
+/// Same as `read_dir_sorted` as a pipeline: entries and their mtime are read
+/// with `tokio::fs` and sent through a channel to a `spawn_blocking` task
+/// inserting them in the map, so both phases run concurrently.
+async fn read_dir_sorted_async(
+    dir: &str,
+    max: usize,
+) -> std::io::Result<BTreeMap<u128, VecDeque<PathBuf>>> {
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<(u128, PathBuf)>(SORT_CHANNEL_CAPACITY);
+
+    let sort_task = tokio::task::spawn_blocking(move || {
+        let mut ordered_files: BTreeMap<u128, VecDeque<PathBuf>> = BTreeMap::new();
+
+        while let Some((duration_nano, path)) = rx.blocking_recv() {
+            ordered_files
+                .entry(duration_nano)
+                .or_default()
+                .push_front(path);
+        }
+
+        ordered_files
+    });
+
+    let mut count = 0;
+
+    while count < max {
+        let mut entries = tokio::fs::read_dir(dir).await?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            // before the entry is processed: a skipped entry still counts
+            if count == max {
+                break;
+            }
+            count += 1;
+            let path = entry.path();
+
+            let modified_date = match tokio::fs::metadata(&path).await {
+                Ok(metadata) => metadata.modified(),
+                Err(e) => {
+                    println!("Can't get metadata for file {path:?}: {e}");
+                    continue;
+                }
+            };
+
+            match modified_date.map(|t| t.duration_since(UNIX_EPOCH)) {
+                Ok(Ok(duration)) => {
+                    // the sort task only stops once all senders are dropped
+                    tx.send((duration.as_nanos(), path)).await.unwrap();
+                }
+                _ => println!("Can't get modified time for file {path:?}"),
+            }
+        }
+    }
+
+    drop(tx);
+    Ok(sort_task.await.unwrap())
+}
+
+/// Same as `read_dir_tokio`, fetching the metadata of each file with a
+/// `spawn_blocking` task, to use the blocking thread pool of tokio.
+fn read_dir_tokio_work_stealing(dir: &str, max: usize) -> std::io::Result<()> {
+    let rt = Runtime::new().unwrap();
+
+    rt.block_on(async {
+        let mut tasks = JoinSet::new();
+        let mut count = 0;
+
+        while count < max {
+            let mut stream = DirStream::new(dir);
+
+            while let Some(event) = stream.next().await {
+                let path = event?;
+                tasks.spawn_blocking(move || metadata(&path));
+
+                count += 1;
+                if count == max {
+                    break;
+                }
+            }
+        }
+
+        while let Some(task) = tasks.join_next().await {
+            let _metadata = task.unwrap();
+        }
+
+        Ok(())
+    })
+}
+
+/// Same as `read_dir_tokio_work_stealing`, with one async task per file calling
+/// `tokio::fs::metadata` instead of a blocking task.
+fn read_dir_tokio_spawn_per_entry(dir: &str, max: usize) -> std::io::Result<()> {
+    let rt = Runtime::new().unwrap();
+
+    rt.block_on(async {
+        let mut tasks = JoinSet::new();
+        let mut count = 0;
+
+        while count < max {
+            let mut stream = DirStream::new(dir);
+
+            while let Some(event) = stream.next().await {
+                let path = event?;
+                tasks.spawn(tokio::fs::metadata(path));
+
+                count += 1;
+                if count == max {
+                    break;
+                }
+            }
+        }
+
+        while let Some(task) = tasks.join_next().await {
+            let _metadata = task.unwrap();
+        }
+
+        Ok(())
+    })
+}
+
+/// Depth of the subdirectories read by `read_dir_recursive` and `read_dir_walk_async`
+const WALK_MAX_DEPTH: usize = 8;
+
+/// Walks the tree of `dir` breadth first, down to `max_depth` levels of
+/// subdirectories, until `max` entries are read.
+/// Returns the number of directories read.
+fn read_dir_recursive(dir: &str, max: usize, max_depth: usize) -> std::io::Result<usize> {
+    let mut dirs = 0;
+    let mut count = 0;
+
+    while count < max {
+        let mut queue = VecDeque::from([(PathBuf::from(dir), 0)]);
+
+        while let Some((dir, depth)) = queue.pop_front() {
+            dirs += 1;
+
+            for entry in std::fs::read_dir(&dir)? {
+                let path = entry?.path();
+
+                if depth < max_depth && metadata(&path).is_ok_and(|metadata| metadata.is_dir()) {
+                    queue.push_back((path, depth + 1));
+                }
+
+                count += 1;
+                if count == max {
+                    return Ok(dirs);
+                }
+            }
+        }
+    }
+
+    Ok(dirs)
+}
+
+/// Number of file descriptors open in this process, read in `/proc/self/fd`
+#[cfg(target_os = "linux")]
+fn open_fds() -> std::io::Result<usize> {
+    // minus the fd reading /proc/self/fd
+    Ok(std::fs::read_dir("/proc/self/fd")?.count() - 1)
+}
+
+/// Walks the tree of `dir` depth first, down to `max_depth` levels of
+/// subdirectories, until `max` entries are read. Unlike `read_dir_recursive`,
+/// the `ReadDir` of each parent stays open while its subdirectories are read,
+/// each one holding a file descriptor: the open fds are counted before and
+/// after each `read_dir`.
+/// Returns the peak number of open file descriptors.
+#[cfg(target_os = "linux")]
+fn read_dir_walk_fd_tracked(dir: &str, max: usize, max_depth: usize) -> std::io::Result<usize> {
+    let mut peak = open_fds()?;
+    let mut count = 0;
+
+    while count < max {
+        let mut stack = vec![std::fs::read_dir(dir)?];
+        peak = peak.max(open_fds()?);
+
+        while let Some(read_dir) = stack.last_mut() {
+            let Some(entry) = read_dir.next() else {
+                stack.pop();
+                continue;
+            };
+            let path = entry?.path();
+
+            if stack.len() <= max_depth && metadata(&path).is_ok_and(|metadata| metadata.is_dir()) {
+                peak = peak.max(open_fds()?);
+                stack.push(std::fs::read_dir(&path)?);
+                peak = peak.max(open_fds()?);
+            }
+
+            count += 1;
+            if count == max {
+                return Ok(peak);
+            }
+        }
+    }
+
+    Ok(peak)
+}
+
+/// Same as `read_dir_recursive` with `tokio::fs`, to be run in a `LocalSet`:
+/// the queue doesn't need to be `Send`.
+async fn read_dir_walk_async(dir: &str, max: usize, max_depth: usize) -> std::io::Result<usize> {
+    let mut dirs = 0;
+    let mut count = 0;
+
+    while count < max {
+        let mut queue = VecDeque::from([(PathBuf::from(dir), 0)]);
+
+        while let Some((dir, depth)) = queue.pop_front() {
+            dirs += 1;
+
+            let mut entries = tokio::fs::read_dir(&dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+
+                if depth < max_depth
+                    && tokio::fs::metadata(&path)
+                        .await
+                        .is_ok_and(|metadata| metadata.is_dir())
+                {
+                    queue.push_back((path, depth + 1));
+                }
+
+                count += 1;
+                if count == max {
+                    return Ok(dirs);
+                }
+            }
+        }
+    }
+
+    Ok(dirs)
+}
+
+/// Timeout of each metadata fetch of `read_dir_tokio_timeout`, never reached on
+/// a local filesystem
+const ENTRY_TIMEOUT_MS: u64 = 100;
+
+/// Same as `read_dir_tokio`, fetching the metadata of each file with a timeout
+/// of `entry_timeout_ms`, to measure the cost of the timeout futures.
+/// Returns the number of entries whose metadata was fetched, and timed out.
+fn read_dir_tokio_timeout(
+    dir: &str,
+    max: usize,
+    entry_timeout_ms: u64,
+) -> std::io::Result<(usize, usize)> {
+    let rt = Runtime::new().unwrap();
+    let entry_timeout = Duration::from_millis(entry_timeout_ms);
+
+    rt.block_on(async {
+        let mut fetched = 0;
+        let mut timed_out = 0;
+
+        while fetched + timed_out < max {
+            let mut stream = DirStream::new(dir);
+
+            while let Some(event) = stream.next().await {
+                let path = event?;
+                match tokio::time::timeout(entry_timeout, tokio::fs::metadata(path)).await {
+                    Ok(_metadata) => fetched += 1,
+                    Err(_elapsed) => timed_out += 1,
+                }
+
+                if fetched + timed_out == max {
+                    break;
+                }
+            }
+        }
+
+        Ok((fetched, timed_out))
+    })
+}
+
+/// Timings of all the runs of one strategy
+#[derive(Serialize, Deserialize)]
+pub struct BenchmarkResult {
+    pub name: String,
+    /// number of files read by each run
+    pub files: usize,
+    #[serde(rename = "durations_ns", with = "durations_ns")]
+    pub durations: Vec<Duration>,
+}
+
+/// Serialization of durations as integers in nanoseconds
+mod durations_ns {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        durations: &[Duration],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(durations.iter().map(Duration::as_nanos))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Duration>, D::Error> {
+        let nanos = Vec::<u64>::deserialize(deserializer)?;
+        Ok(nanos.into_iter().map(Duration::from_nanos).collect())
+    }
+}
+
+impl BenchmarkResult {
+    pub fn min(&self) -> Duration {
+        self.durations.iter().min().copied().unwrap_or_default()
+    }
+
+    pub fn max(&self) -> Duration {
+        self.durations.iter().max().copied().unwrap_or_default()
+    }
+
+    pub fn median(&self) -> Duration {
+        let mut durations = self.durations.clone();
+        durations.sort_unstable();
+
+        match durations.len() {
+            0 => Duration::ZERO,
+            n if n % 2 == 0 => (durations[n / 2 - 1] + durations[n / 2]) / 2,
+            n => durations[n / 2],
+        }
+    }
+
+    /// Sample standard deviation, in milliseconds
+    fn stddev_ms(&self) -> f64 {
+        let n = self.durations.len();
+        if n < 2 {
+            return 0.0;
+        }
+
+        let ms: Vec<f64> = self
+            .durations
+            .iter()
+            .map(|d| d.as_secs_f64() * 1000.0)
+            .collect();
+        let mean = ms.iter().sum::<f64>() / n as f64;
+        let variance = ms.iter().map(|m| (m - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+
+        variance.sqrt()
+    }
+
+    /// Files read per second, based on the median duration
+    fn files_per_sec(&self) -> f64 {
+        let median = self.median().as_secs_f64();
+        if median == 0.0 {
+            return 0.0;
+        }
+
+        self.files as f64 / median
+    }
+}
+
+/// `struct perf_event_attr` of linux/perf_event.h up to `config1`, the first
+/// published version of the struct, not defined by the libc crate
+#[cfg(target_os = "linux")]
+#[repr(C)]
+#[derive(Default)]
+struct PerfEventAttr {
+    type_: u32,
+    size: u32,
+    config: u64,
+    sample_period: u64,
+    sample_type: u64,
+    read_format: u64,
+    /// bit fields: disabled, inherit, pinned, exclusive, exclude_user, exclude_kernel...
+    flags: u64,
+    wakeup_events: u32,
+    bp_type: u32,
+    config1: u64,
+}
+
+/// Counters opened by `PerfCounters`: name, `PERF_TYPE_*` and `PERF_COUNT_*`
+#[cfg(target_os = "linux")]
+const PERF_COUNTERS: [(&str, u32, u64); 3] = [
+    // PERF_TYPE_HARDWARE, PERF_COUNT_HW_CACHE_MISSES
+    ("cache misses", 0, 3),
+    // PERF_TYPE_HARDWARE, PERF_COUNT_HW_INSTRUCTIONS
+    ("instructions", 0, 1),
+    // PERF_TYPE_SOFTWARE, PERF_COUNT_SW_PAGE_FAULTS
+    ("page faults", 1, 2),
+];
+
+/// Performance counters of the calling thread, and of the threads it spawns
+/// once they are started, opened with `perf_event_open(2)`
+#[cfg(target_os = "linux")]
+struct PerfCounters {
+    /// `None` for the counters not supported here, e.g. hardware ones in a VM
+    fds: Vec<Option<OwnedFd>>,
+}
+
+#[cfg(target_os = "linux")]
+impl PerfCounters {
+    /// Opens and starts the counters of `PERF_COUNTERS`
+    fn start() -> Self {
+        const INHERIT: u64 = 1 << 1;
+        const EXCLUDE_HV: u64 = 1 << 6;
+        const PERF_FLAG_FD_CLOEXEC: libc::c_ulong = 1 << 3;
+
+        let fds = PERF_COUNTERS
+            .iter()
+            .map(|&(_, type_, config)| {
+                let attr = PerfEventAttr {
+                    type_,
+                    size: std::mem::size_of::<PerfEventAttr>() as u32,
+                    config,
+                    flags: INHERIT | EXCLUDE_HV,
+                    ..Default::default()
+                };
+                // pid 0 and cpu -1: the calling thread on any CPU
+                let fd = unsafe {
+                    libc::syscall(
+                        libc::SYS_perf_event_open,
+                        &attr,
+                        0,
+                        -1,
+                        -1,
+                        PERF_FLAG_FD_CLOEXEC,
+                    )
+                };
+                (fd >= 0).then(|| unsafe { OwnedFd::from_raw_fd(fd as libc::c_int) })
+            })
+            .collect();
+
+        Self { fds }
+    }
+
+    /// Returns the name and value of each counter, `None` if it is not supported
+    fn stop(self) -> Vec<(&'static str, Option<u64>)> {
+        PERF_COUNTERS
+            .iter()
+            .zip(self.fds)
+            .map(|(&(name, _, _), fd)| {
+                let value = fd.and_then(|fd| {
+                    let mut value = 0u64;
+                    let ret = unsafe {
+                        libc::read(
+                            fd.as_raw_fd(),
+                            &mut value as *mut u64 as *mut libc::c_void,
+                            std::mem::size_of::<u64>(),
+                        )
+                    };
+                    (ret == std::mem::size_of::<u64>() as isize).then_some(value)
+                });
+                (name, value)
+            })
+            .collect()
+    }
+}
+
+/// Allows the process `pid` to trace this one when Yama restricts ptrace,
+/// until dropped
+struct Ptracer;
+
+impl Ptracer {
+    fn allow(pid: u32) -> Self {
+        #[cfg(target_os = "linux")]
+        unsafe {
+            libc::prctl(libc::PR_SET_PTRACER, pid as libc::c_ulong);
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = pid;
+
+        Self
+    }
+}
+
+impl Drop for Ptracer {
+    fn drop(&mut self) {
+        // back to the default: only the ancestors can trace us
+        #[cfg(target_os = "linux")]
+        unsafe {
+            libc::prctl(libc::PR_SET_PTRACER, 0 as libc::c_ulong);
+        }
+    }
+}
+
+/// Syscalls showing that the traced thread spawned threads, or waited for
+/// threads of a pool
+const THREAD_SYSCALLS: [&str; 3] = ["clone", "clone3", "futex"];
+
+/// `strace -c` attached to the main thread, counting the syscalls it issues
+/// until stopped. Threads are not followed: the writer thread would be counted.
+struct SyscallCounter {
+    strace: Child,
+    /// kept open, strace would fail writing its messages to a closed pipe
+    stderr: BufReader<ChildStderr>,
+    /// private directory of the summary, only accessible by us
+    summary_dir: PathBuf,
+    summary: PathBuf,
+    _ptracer: Ptracer,
+}
+
+/// Syscalls counted by `SyscallCounter`
+struct SyscallCount {
+    calls: u64,
+    /// the strategy used other threads, whose syscalls are not counted
+    threaded: bool,
+}
+
+impl SyscallCounter {
+    fn start() -> std::io::Result<Self> {
+        let summary_dir = env::temp_dir().join(format!(
+            "read_dir_perf_strace_{}_{:016x}",
+            std::process::id(),
+            rand::random::<u64>()
+        ));
+        std::fs::DirBuilder::new()
+            .mode(0o700)
+            .create(&summary_dir)?;
+
+        Self::start_in(summary_dir.clone()).inspect_err(|_| {
+            let _ = std::fs::remove_dir_all(&summary_dir);
+        })
+    }
+
+    /// Starts strace, writing its summary in the private directory `summary_dir`
+    fn start_in(summary_dir: PathBuf) -> std::io::Result<Self> {
+        let summary = summary_dir.join("summary");
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&summary)?;
+
+        // strace is started by a shell waiting for a line on its stdin, so it
+        // only attaches once it is allowed to trace us
+        let mut strace = Command::new("sh")
+            .arg("-c")
+            .arg("read -r _ && exec \"$@\"")
+            .arg("sh")
+            .arg("strace")
+            .arg("-c")
+            .arg("-o")
+            .arg(&summary)
+            .arg("-p")
+            .arg(std::process::id().to_string())
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let ptracer = Ptracer::allow(strace.id());
+        strace.stdin.take().expect("piped stdin").write_all(b"\n")?;
+
+        // syscalls are only traced once strace printed "Process N attached"
+        let mut stderr = BufReader::new(strace.stderr.take().expect("piped stderr"));
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if stderr.read_line(&mut line)? == 0 {
+                strace.wait()?;
+                return Err(std::io::Error::other("strace exited before attaching"));
+            }
+
+            if line.contains("attached") {
+                break;
+            }
+        }
+
+        Ok(Self {
+            strace,
+            stderr,
+            summary_dir,
+            summary,
+            _ptracer: ptracer,
+        })
+    }
+
+    /// Detaches strace and returns the total number of syscalls of its summary
+    fn stop(mut self) -> std::io::Result<SyscallCount> {
+        unsafe { libc::kill(self.strace.id() as libc::pid_t, libc::SIGINT) };
+        self.strace.wait()?;
+        drop(self.stderr);
+
+        let summary = std::fs::read_to_string(&self.summary);
+        let _ = std::fs::remove_dir_all(&self.summary_dir);
+        let summary = summary?;
+
+        // % time, seconds, usecs/call, calls, [errors,] syscall or total
+        let calls = summary
+            .lines()
+            .find(|line| line.trim_end().ends_with(" total"))
+            .and_then(|line| line.split_whitespace().nth(3))
+            .and_then(|calls| calls.parse().ok())
+            .ok_or_else(|| std::io::Error::other("no total in strace summary"))?;
+        let threaded = summary
+            .lines()
+            .filter_map(|line| line.split_whitespace().last())
+            .any(|syscall| THREAD_SYSCALLS.contains(&syscall));
+
+        Ok(SyscallCount { calls, threaded })
+    }
+}
+
+/// Runs `f` `runs` times, printing the duration of each run.
+/// Returns the timings and the value returned by the last run.
+fn run_benchmark<T>(
+    name: &str,
+    runs: usize,
+    files: usize,
+    mut f: impl FnMut() -> T,
+) -> (BenchmarkResult, T) {
+    let mut durations = Vec::with_capacity(runs);
+    let mut value = None;
+
+    for _ in 0..runs {
+        let now = Instant::now();
+        value = Some(f());
+        let elapsed = now.elapsed();
+
+        println!(
+            "{name} duration: {}.{:0>3}s",
+            elapsed.as_secs(),
+            elapsed.subsec_millis()
+        );
+
+        durations.push(elapsed);
+    }
+
+    let result = BenchmarkResult {
+        name: name.to_string(),
+        files,
+        durations,
+    };
+
+    (result, value.expect("at least one run"))
+}
+
+/// Returns the release and version of the running kernel, from `uname(2)`
+fn kernel_version() -> String {
+    let mut uts: libc::utsname = unsafe { std::mem::zeroed() };
+    if unsafe { libc::uname(&mut uts) } != 0 {
+        return format!("unknown ({})", std::io::Error::last_os_error());
+    }
+
+    let field = |field: &[libc::c_char]| {
+        unsafe { CStr::from_ptr(field.as_ptr()) }
+            .to_string_lossy()
+            .into_owned()
+    };
+
+    format!(
+        "{} {} {}",
+        field(&uts.sysname),
+        field(&uts.release),
+        field(&uts.version)
+    )
+}
+
+/// Strategies whose first call is measured by --cold-start
+const COLD_START_STRATEGIES: [&str; 2] = ["read_dir", "read_dir_sorted"];
+
+/// Number of entries read by the first call measured by --cold-start
+const COLD_START_ENTRIES: usize = 1000;
+
+/// Writes the script starting this program again to run only `strategy` on
+/// `dir`, given as its first and second arguments
+fn write_cold_start_script(script: &Path) -> std::io::Result<()> {
+    let exe = env::current_exe()?;
+
+    let mut content = b"#!/bin/sh\nexec ".to_vec();
+    content.extend(shell_quote(exe.as_os_str()));
+    content.extend(b" --cold-start-child \"$1\" \"$2\"\n");
+    std::fs::write(script, content)
+}
+
+/// `arg` quoted for a shell: in single quotes, each single quote being closed,
+/// escaped then opened again
+fn shell_quote(arg: &OsStr) -> Vec<u8> {
+    let mut quoted = vec![b'\''];
+    for &byte in arg.as_bytes() {
+        if byte == b'\'' {
+            quoted.extend(b"'\\''");
+        } else {
+            quoted.push(byte);
+        }
+    }
+    quoted.push(b'\'');
+
+    quoted
+}
+
+/// Runs the first call of `strategy` on `dir` in the process started by
+/// --cold-start. Returns its duration.
+pub fn cold_start_child(dir: &str, strategy: &str) -> std::io::Result<Duration> {
+    let start = Instant::now();
+
+    match strategy {
+        "read_dir" => read_dir(&dir.to_string(), COLD_START_ENTRIES)?,
+        "read_dir_sorted" => {
+            read_dir_sorted(dir, COLD_START_ENTRIES)?;
+        }
+        _ => {
+            return Err(std::io::Error::other(format!(
+                "unknown strategy {strategy}"
+            )))
+        }
+    }
+
+    Ok(start.elapsed())
+}
+
+/// Runs `strategy` on `dir` in a new process started by `script`, the cost of
+/// short-lived tools like `ls` or `find`.
+/// Returns the duration of the first call in the new process and of the whole
+/// process, startup included.
+fn measure_cold_start(
+    script: &Path,
+    dir: &str,
+    strategy: &str,
+) -> std::io::Result<(Duration, Duration)> {
+    let start = Instant::now();
+    let output = Command::new("sh")
+        .arg(script)
+        .args([strategy, dir])
+        .output()?;
+    let total = start.elapsed();
+
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!(
+            "{strategy} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    // the child only prints the duration of the call, in nanoseconds
+    let nanos: u64 = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .map_err(std::io::Error::other)?;
+
+    Ok((Duration::from_nanos(nanos), total))
+}
+
+/// Decodes the octal escapes (`\\040` for a space) of a path of `/proc/self/mountinfo`
+fn unescape_mountinfo(path: &str) -> Vec<u8> {
+    let bytes = path.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let escape = bytes.get(i + 1..i + 4).and_then(|octal| {
+            let octal = std::str::from_utf8(octal).ok()?;
+            u8::from_str_radix(octal, 8).ok()
+        });
+
+        match escape {
+            Some(byte) if bytes[i] == b'\\' => {
+                unescaped.push(byte);
+                i += 4;
+            }
+            _ => {
+                unescaped.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    unescaped
+}
+
+/// Returns true if `dir` is on a FUSE filesystem, of type `fuse` or `fuse.X` in
+/// `/proc/self/mountinfo`: their performance is not comparable to the one of
+/// native filesystems.
+fn detect_fuse(dir: &str) -> bool {
+    let Ok(dir) = std::fs::canonicalize(dir) else {
+        return false;
+    };
+    let Ok(mountinfo) = std::fs::read_to_string("/proc/self/mountinfo") else {
+        return false;
+    };
+
+    // the mount point of dir is the longest one containing it
+    // 36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw
+    let mut mount: Option<(PathBuf, &str)> = None;
+    for line in mountinfo.lines() {
+        let Some((fields, fs_fields)) = line.split_once(" - ") else {
+            continue;
+        };
+        let (Some(mount_point), Some(fs_type)) =
+            (fields.split(' ').nth(4), fs_fields.split(' ').next())
+        else {
+            continue;
+        };
+
+        let mount_point = PathBuf::from(OsString::from_vec(unescape_mountinfo(mount_point)));
+        let longer = mount
+            .as_ref()
+            .is_none_or(|(longest, _)| mount_point.as_os_str().len() >= longest.as_os_str().len());
+        if dir.starts_with(&mount_point) && longer {
+            mount = Some((mount_point, fs_type));
+        }
+    }
+
+    mount.is_some_and(|(_, fs_type)| fs_type == "fuse" || fs_type.starts_with("fuse."))
+}
+
+/// Inode number of the initial PID namespace, `PROC_PID_INIT_INO` of the kernel
+#[cfg(target_os = "linux")]
+const PID_NAMESPACE_INIT_INO: u64 = 0xEFFF_FFFC;
+
+/// Inode number of the namespace of type `ns` (`mnt`, `pid`...) of the process `pid`
+#[cfg(target_os = "linux")]
+fn namespace_ino(pid: &str, ns: &str) -> Option<u64> {
+    // the link is `mnt:[4026531840]`
+    let link = std::fs::read_link(format!("/proc/{pid}/ns/{ns}")).ok()?;
+    let link = link.to_str()?;
+    let (_, ino) = link.strip_suffix(']')?.split_once('[')?;
+    ino.parse().ok()
+}
+
+/// Warns if running in a container: inotify watches depend on the mount
+/// namespace and fanotify may report PIDs of another PID namespace, results
+/// may differ from the host. Returns true if a container was detected.
+#[cfg(target_os = "linux")]
+fn check_namespace_context() -> bool {
+    let mut reasons = Vec::new();
+
+    if let Some(ino) = namespace_ino("self", "pid") {
+        if ino != PID_NAMESPACE_INIT_INO {
+            reasons.push("not in the initial PID namespace".to_string());
+        }
+    }
+
+    // only readable with enough privileges
+    if let (Some(own), Some(init)) = (namespace_ino("self", "mnt"), namespace_ino("1", "mnt")) {
+        if own != init {
+            reasons.push("not in the mount namespace of init".to_string());
+        }
+    }
+
+    for marker in ["/.dockerenv", "/run/.containerenv"] {
+        if Path::new(marker).exists() {
+            reasons.push(format!("{marker} exists"));
+        }
+    }
+
+    if reasons.is_empty() {
+        return false;
+    }
+
+    println!(
+        "warning: running in a container ({}), inotify and fanotify results may differ from the host",
+        reasons.join(", ")
+    );
+    true
+}
+
+/// Runs the selected strategies and collects their results
+struct BenchmarkRunner {
+    runs: usize,
+    /// number of files read by each strategy
+    files: usize,
+    /// names of the strategies to run, all of them if `None`
+    selected: Option<Vec<String>>,
+    /// count the syscalls of each strategy with strace
+    strace_counts: bool,
+    /// read the performance counters of each strategy
+    perf_counters: bool,
+    results: Vec<BenchmarkResult>,
+}
+
+impl BenchmarkRunner {
+    fn new(runs: usize, files: usize, selected: Option<Vec<String>>) -> Self {
+        Self {
+            runs,
+            files,
+            selected,
+            strace_counts: false,
+            perf_counters: false,
+            results: Vec::new(),
+        }
+    }
+
+    fn is_selected(&self, name: &str) -> bool {
+        self.selected
+            .as_ref()
+            .is_none_or(|selected| selected.iter().any(|s| s == name))
+    }
+
+    /// Runs the strategy `name` with `f` if it is selected.
+    /// Returns the value returned by its last run.
+    fn run<T>(&mut self, name: &str, f: impl FnMut() -> T) -> Option<T> {
+        if !self.is_selected(name) {
+            return None;
+        }
+
+        let counter = if self.strace_counts {
+            SyscallCounter::start()
+                .inspect_err(|e| println!("{name}: can't count syscalls with strace: {e}"))
+                .ok()
+        } else {
+            None
+        };
+
+        #[cfg(target_os = "linux")]
+        let perf_counters = self.perf_counters.then(PerfCounters::start);
+
+        let (result, value) = run_benchmark(name, self.runs, self.files, f);
+        self.results.push(result);
+
+        #[cfg(target_os = "linux")]
+        if let Some(perf_counters) = perf_counters {
+            let values: Vec<_> = perf_counters
+                .stop()
+                .into_iter()
+                .map(|(counter, value)| match value {
+                    Some(value) => format!("{} {counter}", value / self.runs as u64),
+                    None => format!("{counter} unavailable"),
+                })
+                .collect();
+            println!("{name} perf counters per run: {}", values.join(", "));
+        }
+
+        let injected_errors = INJECTED_ERRORS.swap(0, Ordering::Relaxed);
+        if injected_errors > 0 {
+            println!("{name}: {injected_errors} injected errors");
+        }
+
+        if let Some(counter) = counter {
+            match counter.stop() {
+                Ok(SyscallCount { calls, threaded }) => {
+                    let calls = calls / self.runs as u64;
+                    if threaded {
+                        println!("{name} syscalls: {calls} per run, main thread only");
+                    } else {
+                        println!("{name} syscalls: {calls} per run");
+                    }
+                }
+                Err(e) => println!("{name}: can't count syscalls with strace: {e}"),
+            }
+        }
+
+        Some(value)
+    }
+
+    /// Sets the number of files of the last result, for strategies not reading
+    /// the default number of files
+    fn set_last_files(&mut self, files: usize) {
+        if let Some(result) = self.results.last_mut() {
+            result.files = files;
+        }
+    }
+}
+
+/// Natural logarithm of the gamma function (Lanczos approximation)
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.180_091_729_471_46,
+        -86.505_320_329_416_77,
+        24.014_098_240_830_91,
+        -1.231_739_572_450_155,
+        0.001_208_650_973_866_179,
+        -0.000_005_395_239_384_953,
+    ];
+
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let mut y = x;
+    let mut series = 1.000_000_000_190_015;
+    for c in COEFFICIENTS {
+        y += 1.0;
+        series += c / y;
+    }
+
+    -tmp + (2.506_628_274_631_000_5 * series / x).ln()
+}
+
+/// Continued fraction of the incomplete beta function
+fn beta_continued_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const MAX_ITERATIONS: usize = 200;
+    const EPSILON: f64 = 3e-14;
+    const TINY: f64 = 1e-300;
+
+    let mut c = 1.0;
+    let mut d = 1.0 - (a + b) * x / (a + 1.0);
+    if d.abs() < TINY {
+        d = TINY;
+    }
+    d = 1.0 / d;
+    let mut h = d;
+
+    for m in 1..=MAX_ITERATIONS {
+        let m = m as f64;
+
+        // even step
+        let aa = m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m));
+        d = 1.0 + aa * d;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = 1.0 + aa / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1.0 / d;
+        h *= d * c;
+
+        // odd step
+        let aa = -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0));
+        d = 1.0 + aa * d;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = 1.0 + aa / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1.0 / d;
+        let delta = d * c;
+        h *= delta;
+
+        if (delta - 1.0).abs() < EPSILON {
+            break;
+        }
+    }
+
+    h
+}
+
+/// Regularized incomplete beta function `I_x(a, b)`
+fn incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+
+    // the continued fraction converges quickly on this side only
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_continued_fraction(a, b, x) / a
+    } else {
+        1.0 - front * beta_continued_fraction(b, a, 1.0 - x) / b
+    }
+}
+
+/// Result of a Welch's t-test between the durations of two strategies
+struct WelchTTest {
+    t: f64,
+    degrees_of_freedom: f64,
+    /// two-tailed
+    p_value: f64,
+}
+
+fn welch_t_test(a: &[Duration], b: &[Duration]) -> WelchTTest {
+    let mean_variance = |durations: &[Duration]| {
+        let n = durations.len() as f64;
+        let mean = durations.iter().map(Duration::as_secs_f64).sum::<f64>() / n;
+        let variance = durations
+            .iter()
+            .map(|d| (d.as_secs_f64() - mean).powi(2))
+            .sum::<f64>()
+            / (n - 1.0);
+        (n, mean, variance)
+    };
+
+    let (n_a, mean_a, var_a) = mean_variance(a);
+    let (n_b, mean_b, var_b) = mean_variance(b);
+
+    let se_a = var_a / n_a;
+    let se_b = var_b / n_b;
+    let se = se_a + se_b;
+
+    if se == 0.0 {
+        // no variance at all: any difference is significant
+        let p_value = if mean_a == mean_b { 1.0 } else { 0.0 };
+        return WelchTTest {
+            t: 0.0,
+            degrees_of_freedom: n_a + n_b - 2.0,
+            p_value,
+        };
+    }
+
+    let t = (mean_a - mean_b) / se.sqrt();
+    // Welch-Satterthwaite equation
+    let degrees_of_freedom = se.powi(2) / (se_a.powi(2) / (n_a - 1.0) + se_b.powi(2) / (n_b - 1.0));
+    let p_value = incomplete_beta(
+        degrees_of_freedom / 2.0,
+        0.5,
+        degrees_of_freedom / (degrees_of_freedom + t * t),
+    );
+
+    WelchTTest {
+        t,
+        degrees_of_freedom,
+        p_value,
+    }
+}
+
+/// Prints if the durations of the strategies `a` and `b` are significantly different
+pub fn print_welch_t_test(a: &BenchmarkResult, b: &BenchmarkResult) {
+    let test = welch_t_test(&a.durations, &b.durations);
+
+    println!(
+        "{} vs {}: t = {:.3}, df = {:.1}, p = {:.4}",
+        a.name, b.name, test.t, test.degrees_of_freedom, test.p_value
+    );
+
+    if test.p_value > 0.05 {
+        println!("no significant difference");
+    } else {
+        let faster = if test.t < 0.0 { a } else { b };
+        println!("significant difference, {} is faster", faster.name);
+    }
+}
+
+/// Results of a `BenchmarkSuite`, printed as a table by `Display`
+#[derive(Serialize, Deserialize)]
+pub struct BenchmarkReport {
+    /// release and version of the kernel the benchmarks ran on
+    pub kernel: String,
+    /// the directory is on a FUSE filesystem
+    pub fuse: bool,
+    pub results: Vec<BenchmarkResult>,
+}
+
+impl BenchmarkReport {
+    /// The report as a JSON object, the durations in nanoseconds
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("report serialization can't fail") + "\n"
+    }
+
+    /// The report as CSV, one line per strategy, the durations in nanoseconds
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "name,runs,files,min_ns,median_ns,max_ns,stddev_ms,files_per_sec,filesystem,kernel\n",
+        );
+        let filesystem = if self.fuse { "FUSE" } else { "native" };
+        // the version of the kernel usually has commas, in its build date
+        let kernel = format!("\"{}\"", self.kernel.replace('"', "\"\""));
+
+        for r in &self.results {
+            csv += &format!(
+                "{},{},{},{},{},{},{:.3},{:.0},{filesystem},{kernel}\n",
+                r.name,
+                r.durations.len(),
+                r.files,
+                r.min().as_nanos(),
+                r.median().as_nanos(),
+                r.max().as_nanos(),
+                r.stddev_ms(),
+                r.files_per_sec()
+            );
+        }
+
+        csv
+    }
+}
+
+/// Reads the median duration of each strategy of a report written by `to_json`
+pub fn read_baseline(path: &Path) -> Result<HashMap<String, Duration>, String> {
+    let json = std::fs::read_to_string(path).map_err(|e| format!("can't read {path:?}: {e}"))?;
+    let report: BenchmarkReport =
+        serde_json::from_str(&json).map_err(|e| format!("invalid report {path:?}: {e}"))?;
+
+    Ok(report
+        .results
+        .into_iter()
+        .map(|result| (result.name.clone(), result.median()))
+        .collect())
+}
+
+/// Prints, for each strategy of `results` also in `baseline`, if its median
+/// duration is more than `threshold` percent longer than in the baseline.
+/// Returns true if a strategy regressed.
+pub fn print_regression_check(
+    results: &[BenchmarkResult],
+    baseline: &HashMap<String, Duration>,
+    threshold: f64,
+) -> bool {
+    let ms = |d: Duration| format!("{:.3}", d.as_secs_f64() * 1000.0);
+    let mut regressed = false;
+
+    let header = [
+        "Strategy",
+        "Baseline (ms)",
+        "Median (ms)",
+        "Change",
+        "Result",
+    ];
+    let mut rows = Vec::new();
+
+    for result in results {
+        let Some(&previous) = baseline.get(&result.name) else {
+            continue;
+        };
+
+        let median = result.median();
+        let change = if previous.is_zero() {
+            0.0
+        } else {
+            (median.as_secs_f64() / previous.as_secs_f64() - 1.0) * 100.0
+        };
+        let failed = change > threshold;
+        regressed |= failed;
+
+        rows.push([
+            result.name.clone(),
+            ms(previous),
+            ms(median),
+            format!("{change:+.1}%"),
+            if failed { "FAIL" } else { "pass" }.to_string(),
+        ]);
+    }
+
+    println!("regressions beyond {threshold}% of the baseline:");
+    print!("{}", format_table(&header, &rows));
+
+    regressed
+}
+
+/// `header` and `rows` as a table, each cell padded to the widest one of its
+/// column, the header separated from the rows by a line
+fn format_table<const N: usize>(header: &[&str; N], rows: &[[String; N]]) -> String {
+    let mut widths = header.map(str::len);
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let format_row = |row: &[&str; N]| {
+        row.iter()
+            .zip(widths)
+            .map(|(cell, width)| format!(" {cell:>width$} "))
+            .collect::<Vec<_>>()
+            .join("|")
+    };
+
+    let separator = widths
+        .iter()
+        .map(|width| "-".repeat(width + 2))
+        .collect::<Vec<_>>()
+        .join("+");
+
+    let mut table = format!("{}\n{separator}\n", format_row(header));
+    for row in rows {
+        table += &format_row(&row.each_ref().map(String::as_str));
+        table.push('\n');
+    }
+
+    table
+}
+
+/// All the results in a table
+impl std::fmt::Display for BenchmarkReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ms = |d: Duration| format!("{:.3}", d.as_secs_f64() * 1000.0);
+
+        let header = [
+            "Strategy",
+            "Runs",
+            "Min (ms)",
+            "Median (ms)",
+            "Max (ms)",
+            "Stddev",
+            "Files/s",
+            "Filesystem",
+        ];
+
+        let filesystem = if self.fuse { "FUSE" } else { "native" };
+        let rows: Vec<[String; 8]> = self
+            .results
+            .iter()
+            .map(|r| {
+                [
+                    r.name.clone(),
+                    r.durations.len().to_string(),
+                    ms(r.min()),
+                    ms(r.median()),
+                    ms(r.max()),
+                    format!("{:.3}", r.stddev_ms()),
+                    format!("{:.0}", r.files_per_sec()),
+                    filesystem.to_string(),
+                ]
+            })
+            .collect();
+
+        writeln!(f, "kernel: {}", self.kernel)?;
+        write!(f, "{}", format_table(&header, &rows))
+    }
+}
+
+/// Benchmarks of all the strategies selected by the options: creates the
+/// directory, fills it with the writer thread while the strategies run, then
+/// stops the writer and removes the directory.
+pub struct BenchmarkSuite {
+    options: Options,
+    /// number of files read by each strategy
+    max_files: usize,
+}
+
+impl BenchmarkSuite {
+    pub fn new(options: Options, max_files: usize) -> Self {
+        Self { options, max_files }
+    }
+
+    pub fn run_all(&self) -> BenchmarkReport {
+        let options = &self.options;
+        let dir = options.dir.clone();
+        let max_files = self.max_files;
+
+        // results depend a lot on the kernel, always keep it with them
+        let kernel = kernel_version();
+        println!("kernel: {kernel}");
+
+        #[cfg(target_os = "linux")]
+        check_namespace_context();
+
+        // an existing directory is only kept, and reused, with --overwrite
+        let dir_exists = std::fs::metadata(&dir).is_ok();
+        if dir_exists && !options.create.overwrite {
+            panic!("Error: path {dir} exists");
+        }
+
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let fuse = detect_fuse(&dir);
+        if fuse {
+            println!("warning: {dir} is on a FUSE filesystem, results are not comparable to native filesystems");
+        }
+
+        let thread_dir = dir.clone();
+        let thread_options = options.create.clone();
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        // thread to create a lot of files continuously
+        let gen_thread = std::thread::spawn(|| {
+            create_files(rx, thread_dir, thread_options);
+        });
+
+        // wait 1s to create some initial files
+        std::thread::sleep(Duration::from_secs(1));
+
+        // set after spawning the writer thread so only the benchmarks, and the
+        // threads they spawn, get this priority
+        #[cfg(target_os = "linux")]
+        if let Some(priority) = options.io_priority {
+            set_io_priority(priority).unwrap();
+        }
+
+        if options.sudo_create {
+            if let Err(e) = create_files_as_root(&dir, SUDO_CREATE_FILES) {
+                println!("Can't create files as root: {e}");
+            }
+        }
+
+        if options.mtime_delta {
+            let delta = read_dir_mtime_delta(&dir).unwrap();
+            println!("mtime delta: {:.3}s", delta.as_secs_f64());
+        }
+
+        if options.mtime_check {
+            match read_dir_mtime_monotonic_check(&dir) {
+                Ok(true) => println!("mtime check: mtime order matches creation order"),
+                Ok(false) => println!("mtime check: not enough files"),
+                Err(e) => println!("mtime check: {e}, read_dir_sorted order is not creation order"),
+            }
+        }
+
+        // in a directory of its own, the files of the writer thread would be first
+        if options.inotify_startup {
+            let probe_dir = format!("{dir}.inotify_startup");
+            std::fs::create_dir_all(&probe_dir).unwrap();
+            let latency = measure_inotify_startup_latency(&probe_dir);
+            std::fs::remove_dir_all(&probe_dir).unwrap();
+            println!("inotify startup latency: {latency:?}");
+        }
+
+        if options.cold_start {
+            let script = PathBuf::from(format!("{dir}.cold_start.sh"));
+            write_cold_start_script(&script).unwrap();
+            for strategy in COLD_START_STRATEGIES {
+                match measure_cold_start(&script, &dir, strategy) {
+                    Ok((first_call, total)) => println!(
+                        "cold start {strategy}: first call {first_call:?}, {total:?} with the process startup"
+                    ),
+                    Err(e) => println!("cold start {strategy}: {e}"),
+                }
+            }
+            std::fs::remove_file(&script).unwrap();
+        }
+
+        if let Some(slots) = options.create.truncate {
+            let probe_dir = format!("{dir}.truncate");
+            std::fs::create_dir_all(&probe_dir).unwrap();
+            let (created, truncated) = measure_truncate_event_latency(&probe_dir, slots);
+            std::fs::remove_dir_all(&probe_dir).unwrap();
+            println!(
+                "CLOSE_WRITE latency: {created:?} for new files, {truncated:?} for truncated files"
+            );
+        }
+
+        if options.total_size {
+            let total = total_dir_size(&dir).unwrap();
+            println!("total size: {total} bytes ({})", format_size(total));
+        }
+
+        if options.inotify_sequence_check {
+            let probe_dir = format!("{dir}.inotify_sequence");
+            std::fs::create_dir_all(&probe_dir).unwrap();
+            if read_inotify_sequence_check(&probe_dir, INOTIFY_SEQUENCE_FILES) {
+                println!("inotify sequence: {INOTIFY_SEQUENCE_FILES} events in creation order");
+            }
+            std::fs::remove_dir_all(&probe_dir).unwrap();
+        }
+
+        if options.count_types {
+            let counts = count_entry_types(&dir).unwrap();
+            println!(
+                "entries: {} files, {} dirs, {} symlinks, {} other",
+                counts.files, counts.dirs, counts.symlinks, counts.other
+            );
+        }
+
+        if options.group_by_extension {
+            let groups = group_by_extension(&dir).unwrap();
+            let mut counts: Vec<_> = groups
+                .iter()
+                .map(|(extension, paths)| (extension, paths.len()))
+                .collect();
+            counts.sort_unstable();
+            for (extension, count) in counts {
+                println!("extension {extension:?}: {count} entries");
+            }
+        }
+
+        let selected = options.compare.clone().map(|(a, b)| vec![a, b]);
+        // only once the diagnostics are done, they must see the real filesystem
+        if let Some(error_rate) = options.error_rate {
+            // already set by a previous run of the suite
+            let _ = ERROR_RATE.set(error_rate);
+        }
+
+        let mut runner = BenchmarkRunner::new(options.runs, max_files, selected);
+        runner.strace_counts = options.strace_counts;
+        runner.perf_counters = options.perf_counters;
+
+        self.run_strategies(&mut runner);
+
+        let _ = tx.send(());
+        gen_thread.join().unwrap();
+
+        if !dir_exists {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+
+        BenchmarkReport {
+            kernel,
+            fuse,
+            results: runner.results,
+        }
+    }
+
+    /// Runs the selected strategies, the writer thread filling the directory
+    fn run_strategies(&self, runner: &mut BenchmarkRunner) {
+        let options = &self.options;
+        let dir = options.dir.clone();
+        let max_files = self.max_files;
+
+        // no filesystem call, first in the comparison table as the lower bound
+        runner.run("read_dir_noop", || read_dir_noop(max_files));
+
+        // readdir sync unsorted
+        runner.run("read_dir", || read_dir(&dir, max_files).unwrap());
+
+        // readdir sync unsorted, throttled by a token bucket
+        if let Some(waits) = runner.run("read_dir_rate_limited", || {
+            read_dir_rate_limited(&dir, max_files, RATE_LIMIT).unwrap()
+        }) {
+            println!("read_dir_rate_limited: {waits} waits for a token at {RATE_LIMIT} entries/s");
+        }
+
+        // readdir sync unsorted, percentiles of the time spent on each entry
+        if let Some(stats) = runner.run("read_dir_latency_percentiles", || {
+            measure_per_entry_latency(&dir, max_files).unwrap()
+        }) {
+            println!(
+                "read_dir_latency_percentiles: p50 {:?}, p90 {:?}, p95 {:?}, p99 {:?}, p99.9 {:?}",
+                stats.p50, stats.p90, stats.p95, stats.p99, stats.p999
+            );
+        }
+
+        // readdir sync unsorted, with a progress bar
+        #[cfg(feature = "progress")]
+        runner.run("read_dir_with_progress", || {
+            read_dir_with_progress(&dir, max_files).unwrap()
+        });
+
+        // readdir sync unsorted, with iterator adapters
+        runner.run("read_dir_iter", || read_dir_iter(&dir, max_files).unwrap());
+        runner.run("read_dir_iter_txt", || {
+            read_dir_iter_txt(&dir, max_files).unwrap()
+        });
+
+        // readdir sync, stopped once a limit of entries or size is exceeded
+        if let Some(result) = runner.run("read_dir_quota_check", || {
+            read_dir_quota_check(&dir, QUOTA_MAX_INODES, QUOTA_MAX_BYTES).unwrap()
+        }) {
+            runner.set_last_files(result.inodes as usize);
+            println!(
+                "read_dir_quota_check: {} entries, {} scanned, {} free inodes on the filesystem",
+                result.inodes,
+                format_size(result.bytes),
+                result.free_inodes
+            );
+            if let Some((limit, path)) = result.exceeded {
+                println!("    {limit:?} limit exceeded at {path:?}");
+            }
+        }
+
+        // readdir sync sorted
+        if runner.is_selected("read_dir_sorted") {
+            let probe_dir = format!("{dir}.mtime_resolution");
+            std::fs::create_dir_all(&probe_dir).unwrap();
+            let start = Instant::now();
+            let resolution = measure_mtime_resolution(&probe_dir);
+            let elapsed = start.elapsed();
+            std::fs::remove_dir_all(&probe_dir).unwrap();
+
+            if resolution.is_zero() {
+                println!(
+                    "mtime resolution: more than {elapsed:?}, {MTIME_RESOLUTION_FILES} files created with the same mtime"
+                );
+            } else {
+                println!("mtime resolution: {resolution:?}");
+            }
+            if resolution >= Duration::from_secs(1) {
+                println!(
+                    "warning: read_dir_sorted can't order files created within the same second"
+                );
+            }
+        }
+
+        runner.run("read_dir_sorted", || {
+            read_dir_sorted(&dir, max_files).unwrap()
+        });
+
+        // readdir sync sorted, all the paths read before fetching their metadata
+        runner.run("read_dir_sorted_two_pass", || {
+            read_dir_sorted_two_pass(&dir, max_files).unwrap()
+        });
+
+        // map of read_dir_sorted serialized with bincode, and deserialized
+        if runner.is_selected("read_dir_sorted_serialize")
+            || runner.is_selected("read_dir_sorted_deserialize")
+        {
+            let sorted = read_dir_sorted(&dir, max_files).unwrap();
+            if let Some(bytes) = runner.run("read_dir_sorted_serialize", || {
+                serialize_sorted_result(&sorted)
+            }) {
+                println!(
+                    "read_dir_sorted_serialize: {}",
+                    format_size(bytes.len() as u64)
+                );
+            }
+
+            let bytes = serialize_sorted_result(&sorted);
+            runner.run("read_dir_sorted_deserialize", || {
+                deserialize_sorted_result(&bytes)
+            });
+        }
+
+        // readdir sync sorted, only the files created since a first sort
+        if runner.is_selected("read_dir_sorted_incremental") {
+            let mut seen = read_dir_sorted(&dir, max_files).unwrap();
+            if let Some(new) = runner.run("read_dir_sorted_incremental", || {
+                read_dir_sorted_incremental(&dir, &mut seen, max_files).unwrap()
+            }) {
+                println!("read_dir_sorted_incremental: {new} new files sorted");
+            }
+        }
+
+        // readdir sync sorted, the map of the previous run being reused with --runs
+        runner.run("read_dir_sorted_thread_local", || {
+            read_dir_sorted_thread_local(&dir, max_files).unwrap()
+        });
+
+        // readdir sync, k newest files of the whole directory
+        for k in TOPK_SIZES {
+            let name = format!("read_dir_topk_{k}");
+            if let Some((scanned, _newest)) = runner.run(&name, || read_dir_topk(&dir, k).unwrap())
+            {
+                // the whole directory is scanned, not max_files entries
+                runner.set_last_files(scanned);
+                println!("{name}: {scanned} entries scanned");
+            }
+        }
+
+        // readdir sync sorted by the number in the file names
+        runner.run("read_dir_sparse_sorted", || {
+            read_dir_sparse_sorted(&dir, max_files).unwrap()
+        });
+
+        // readdir sync sorted, with the index of the mtime of each path
+        runner.run("read_dir_sorted_with_index", || {
+            read_dir_sorted_with_index(&dir, max_files).unwrap()
+        });
+
+        // readdir sync sorted, sorted paths written to a file
+        if runner.is_selected("read_dir_sorted_stream_to_file") {
+            let output = PathBuf::from(format!("{dir}.sorted"));
+            runner.run("read_dir_sorted_stream_to_file", || {
+                read_dir_sorted_stream_to_file(&dir, max_files, &output).unwrap()
+            });
+            std::fs::remove_file(&output).unwrap();
+        }
+
+        // readdir sync sorted, mtime ties broken by inode number
+        runner.run("read_dir_sorted_tiebreak", || {
+            read_dir_sorted_tiebreak(&dir, max_files).unwrap()
+        });
+
+        // readdir sync sorted by number of hard links
+        if let Some(ordered_files) = runner.run("read_dir_sorted_by_nlink", || {
+            read_dir_sorted_by_nlink(&dir, max_files).unwrap()
+        }) {
+            if let Some(Reverse(nlink)) = ordered_files.keys().next() {
+                println!("read_dir_sorted_by_nlink: at most {nlink} links");
+            }
+        }
+
+        // readdir sync sorted by allocated blocks
+        if let Some(ordered_files) = runner.run("read_dir_sorted_by_blocks", || {
+            read_dir_sorted_by_blocks(&dir, max_files).unwrap()
+        }) {
+            if let (Some(min), Some(max)) =
+                (ordered_files.keys().next(), ordered_files.keys().last())
+            {
+                println!("read_dir_sorted_by_blocks: from {min} to {max} blocks of 512 bytes");
+            }
+        }
+
+        // readdir sync sorted by owner, more than one with --sudo-create
+        if let Some(ordered_files) = runner.run("read_dir_sorted_by_uid", || {
+            read_dir_sorted_by_uid(&dir, max_files).unwrap()
+        }) {
+            println!("read_dir_sorted_by_uid: {} owners", ordered_files.len());
+        }
+
+        if let Some(ordered_files) = runner.run("read_dir_sorted_by_name_length", || {
+            read_dir_sorted_by_name_length(&dir, max_files).unwrap()
+        }) {
+            if let (Some(min), Some(max)) =
+                (ordered_files.keys().next(), ordered_files.keys().last())
+            {
+                println!("read_dir_sorted_by_name_length: names from {min} to {max} bytes");
+            }
+        }
+
+        // readdir sync sorted, paths allocated in an arena
+        runner.run("read_dir_sorted_bump", || {
+            read_dir_sorted_bump(&dir, max_files).unwrap()
+        });
+
+        // readdir sync sorted, memory allocated on the local NUMA node
+        #[cfg(target_os = "linux")]
+        if numa_available() {
+            runner.run("read_dir_numa_sorted", || {
+                read_dir_numa_sorted(&dir, max_files).unwrap()
+            });
+        } else if runner.is_selected("read_dir_numa_sorted") {
+            println!("read_dir_numa_sorted: skipped, NUMA is not available");
+        }
+
+        // readdir sync sorted by chunks
+        if let Some(chunks) = runner.run("read_dir_sorted_chunk", || {
+            read_dir_sorted_chunk(&dir, max_files, SORT_CHUNK_SIZE).unwrap()
+        }) {
+            println!("read_dir_sorted_chunk: {chunks} chunks of {SORT_CHUNK_SIZE}");
+        }
+
+        // readdir sync sorted with an external merge sort
+        if runner.is_selected("read_dir_lazy_sorted") {
+            let runs_dir = PathBuf::from(format!("{dir}.runs"));
+            std::fs::create_dir_all(&runs_dir).unwrap();
+            if let Some(sorted_runs) = runner.run("read_dir_lazy_sorted", || {
+                read_dir_lazy_sorted(&dir, max_files, &runs_dir, SORT_CHUNK_SIZE).unwrap()
+            }) {
+                println!("read_dir_lazy_sorted: {sorted_runs} runs of {SORT_CHUNK_SIZE}");
+            }
+            std::fs::remove_dir_all(&runs_dir).unwrap();
+        }
+
+        // readdir sync sorted, same directory scanned several times in parallel then merged
+        let merge_dirs = [dir.as_str(); MERGE_SCANS];
+        runner.run("read_dir_sorted_merge", || {
+            read_dir_sorted_merge(&merge_dirs, max_files).unwrap()
+        });
+
+        // same scans as read_dir_sorted_merge, serially
+        runner.run("read_dir_sorted_serial", || {
+            for _ in 0..MERGE_SCANS {
+                read_dir_sorted(&dir, max_files / MERGE_SCANS).unwrap();
+            }
+        });
+
+        // readdir sync sorted, incrementally updating a persisted sorted list
+        if runner.is_selected("read_dir_sorted_persisted") {
+            let cache_file = PathBuf::from(format!("{dir}.sorted_cache"));
+            // first call is a full scan, only the incremental path is measured
+            read_dir_sorted_persisted(&dir, max_files, &cache_file).unwrap();
+            if let Some(new_entries) = runner.run("read_dir_sorted_persisted", || {
+                read_dir_sorted_persisted(&dir, max_files, &cache_file).unwrap()
+            }) {
+                println!("read_dir_sorted_persisted: {new_entries} new entries");
+            }
+            let _ = std::fs::remove_file(&cache_file);
+        }
+
+        // readdir sync, new entries binary searched in a memory mapped sorted index
+        if runner.is_selected("read_dir_sorted_mmap") {
+            let index_file = PathBuf::from(format!("{dir}.sorted_index"));
+            let indexed = write_sorted_index(&dir, max_files, &index_file).unwrap();
+            if let Some(new_entries) = runner.run("read_dir_sorted_mmap", || {
+                read_dir_sorted_mmap(&dir, max_files, &index_file).unwrap()
+            }) {
+                println!("read_dir_sorted_mmap: {new_entries} new entries, {indexed} indexed");
+            }
+            std::fs::remove_file(&index_file).unwrap();
+        }
+
+        // readdir, entries processed by several threads sharing an atomic counter
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        if let Some(count) = runner.run("read_dir_atomic_concurrent", || {
+            read_dir_atomic_concurrent(&dir, max_files, threads).unwrap()
+        }) {
+            println!("read_dir_atomic_concurrent: {count} entries counted by {threads} threads");
+        }
+
+        // readdir, entries sorted by several threads sharing a map behind a mutex
+        runner.run("read_dir_concurrent_sorted", || {
+            read_dir_concurrent_sorted(&dir, max_files, threads).unwrap()
+        });
+
+        // readdir sync with a shared lock on each file
+        if let Some(skipped) = runner.run("read_dir_with_lock", || {
+            read_dir_with_lock(&dir, max_files).unwrap()
+        }) {
+            println!("read_dir_with_lock: {skipped} locked files skipped");
+        }
+
+        // readdir sync with a shared lock on each file, retried with a backoff
+        if let Some(skipped) = runner.run("read_dir_with_lock_retry", || {
+            read_dir_with_lock_retry(&dir, max_files, &LOCK_RETRY_POLICY).unwrap()
+        }) {
+            println!(
+                "read_dir_with_lock_retry: {} locked files skipped after {} retries",
+                skipped.len(),
+                LOCK_RETRY_POLICY.max_retries
+            );
+            for path in &skipped {
+                println!("    gave up on {path:?}");
+            }
+        }
+
+        // readdir sync with POSIX_FADV_SEQUENTIAL on the directory fd
+        #[cfg(target_os = "linux")]
+        runner.run("read_dir_with_fadvise", || {
+            read_dir_with_fadvise(&dir, max_files).unwrap()
+        });
+
+        // readdir sync, same directory scanned several times, opened once
+        let per_scan = max_files / DIR_FD_CACHE_SCANS;
+        if let Some(count) = runner.run("read_dir_fd_cache", || {
+            read_dir_fd_cache(&dir, per_scan, DIR_FD_CACHE_SCANS).unwrap()
+        }) {
+            runner.set_last_files(count);
+        }
+
+        // same scans as read_dir_fd_cache, the directory opened for each of them
+        if let Some(count) = runner.run("read_dir_fd_uncached", || {
+            read_dir_fd_uncached(&dir, per_scan, DIR_FD_CACHE_SCANS).unwrap()
+        }) {
+            runner.set_last_files(count);
+        }
+
+        // getdents64 on a directory fd opened with O_LARGEFILE
+        #[cfg(target_os = "linux")]
+        runner.run("read_dir_largefile", || {
+            read_dir_largefile(&dir, max_files).unwrap()
+        });
+
+        // readdir sync, opening each file with its path
+        runner.run("read_dir_with_open", || {
+            read_dir_with_open(&dir, max_files).unwrap()
+        });
+
+        // getdents64, opening each file relative to the directory fd
+        #[cfg(target_os = "linux")]
+        runner.run("read_dir_with_openat", || {
+            read_dir_with_openat(&dir, max_files).unwrap()
+        });
+
+        // getdents64, metadata of each file with fstatat
+        #[cfg(target_os = "linux")]
+        runner.run("read_dir_with_metadata", || {
+            read_dir_with_metadata(&dir, max_files).unwrap()
+        });
+
+        // getdents64, mtime and inode of each file with statx
+        #[cfg(target_os = "linux")]
+        runner.run("read_dir_with_statx", || {
+            read_dir_with_statx(&dir, max_files).unwrap()
+        });
+
+        // readdir sync, changes since a previous scan, the directory growing meanwhile
+        if runner.is_selected("read_dir_delta") {
+            match diff_dir(&dir, &HashSet::new()) {
+                Ok((previous, _)) => {
+                    match runner.run("read_dir_delta", || diff_dir(&dir, &previous)) {
+                        Some(Ok((added, removed))) => println!(
+                            "read_dir_delta: {} added, {} removed since a scan of {} files",
+                            added.len(),
+                            removed.len(),
+                            previous.len()
+                        ),
+                        Some(Err(e)) => println!("read_dir_delta: can't scan {dir}: {e}"),
+                        None => {}
+                    }
+                }
+                Err(e) => println!("read_dir_delta: skipped, can't scan {dir}: {e}"),
+            }
+        }
+
+        // readdir sync, then metadata of each path, one after the other
+        runner.run("read_dir_collected_metadata", || {
+            read_dir_collected_metadata(&dir, max_files).unwrap()
+        });
+
+        // readdir sync, then metadata of the paths fetched by the rayon thread pool
+        runner.run("read_dir_rayon_metadata", || {
+            read_dir_rayon_metadata(&dir, max_files).unwrap()
+        });
+
+        // readdir sync, then mtime of the paths fetched by batches with io_uring
+        #[cfg(target_os = "linux")]
+        if io_uring_available() {
+            runner.run("read_dir_with_io_uring", || {
+                read_dir_with_io_uring(&dir, max_files).unwrap()
+            });
+        } else if runner.is_selected("read_dir_with_io_uring") {
+            println!("read_dir_with_io_uring: skipped, io_uring is not available");
+        }
+
+        // readdir sync, hashing the content of each file
+        runner.run("read_dir_with_checksum", || {
+            read_dir_with_checksum(&dir, max_files).unwrap()
+        });
+
+        // readdir sync, content of each file sent to /dev/null by the kernel
+        #[cfg(target_os = "linux")]
+        runner.run("read_dir_with_sendfile", || {
+            read_dir_with_sendfile(&dir, max_files).unwrap()
+        });
+
+        // readdir sync, content of each file sent to /dev/null through a pipe
+        #[cfg(target_os = "linux")]
+        runner.run("read_dir_with_splice", || {
+            read_dir_with_splice(&dir, max_files).unwrap()
+        });
+
+        // readdir sync, reading the ACL of each file
+        #[cfg(target_os = "linux")]
+        if let Some(acl_entries) = runner.run("read_dir_with_acl", || {
+            read_dir_with_acl(&dir, max_files).unwrap()
+        }) {
+            println!("read_dir_with_acl: {acl_entries} ACL entries");
+        }
+
+        // readdir async (tokio)
+        runner.run("read_dir_tokio", || {
+            read_dir_tokio(&dir, max_files).unwrap()
+        });
+
+        // readdir async (tokio), entries collected with stream adapters
+        let rt = Runtime::new().unwrap();
+        runner.run("read_dir_async_collect", || {
+            rt.block_on(read_dir_async_collect(&dir, max_files))
+                .unwrap()
+        });
+
+        // readdir async (tokio) sorted, reading and sorting phases in a pipeline
+        runner.run("read_dir_sorted_async", || {
+            rt.block_on(read_dir_sorted_async(&dir, max_files)).unwrap()
+        });
+
+        // readdir async (tokio), metadata fetched in the blocking thread pool
+        runner.run("read_dir_tokio_work_stealing", || {
+            read_dir_tokio_work_stealing(&dir, max_files).unwrap()
+        });
+
+        // readdir async (tokio), metadata fetched by one async task per file
+        runner.run("read_dir_tokio_spawn_per_entry", || {
+            read_dir_tokio_spawn_per_entry(&dir, max_files).unwrap()
+        });
+
+        // readdir sync, tree walked breadth first
+        runner.run("read_dir_recursive", || {
+            read_dir_recursive(&dir, max_files, WALK_MAX_DEPTH).unwrap()
+        });
+
+        // readdir sync, tree walked depth first, counting the open file descriptors
+        #[cfg(target_os = "linux")]
+        if let Some(peak) = runner.run("read_dir_walk_fd_tracked", || {
+            read_dir_walk_fd_tracked(&dir, max_files, WALK_MAX_DEPTH).unwrap()
+        }) {
+            println!("read_dir_walk_fd_tracked: at most {peak} open file descriptors");
+        }
+
+        // readdir async (tokio), tree walked breadth first in a LocalSet
+        let local = LocalSet::new();
+        runner.run("read_dir_walk_async", || {
+            local
+                .block_on(&rt, read_dir_walk_async(&dir, max_files, WALK_MAX_DEPTH))
+                .unwrap()
+        });
+
+        // readdir async (tokio), metadata fetched with a timeout
+        if let Some((fetched, timed_out)) = runner.run("read_dir_tokio_timeout", || {
+            read_dir_tokio_timeout(&dir, max_files, ENTRY_TIMEOUT_MS).unwrap()
+        }) {
+            println!("read_dir_tokio_timeout: {fetched} entries, {timed_out} timed out");
+        }
+
+        // hard links don't generate CLOSE_WRITE events, these strategies would wait forever
+        if options.create.hardlink {
+            println!("CLOSE_WRITE inotify strategies skipped with --hardlink");
+        } else {
+            // sync inotify
+            if let Some(overflow) = runner.run("read_inotify", || read_inotify(&dir, max_files)) {
+                overflow.print("read_inotify", max_files);
+            }
+
+            // sync inotify, batch sizes by buffer size. Each read is a wakeup,
+            // read_inotify_batch_8096 has the buffer of read_inotify
+            for buffer_size in INOTIFY_BATCH_BUFFER_SIZES {
+                let name = format!("read_inotify_batch_{buffer_size}");
+                if let Some(stats) =
+                    runner.run(&name, || read_inotify_batch(&dir, max_files, buffer_size))
+                {
+                    let events_per_wakeup = stats.average();
+                    println!(
+                        "{name}: {} wakeups for {} events, {events_per_wakeup:.1} events per wakeup",
+                        stats.batches, stats.events
+                    );
+                    if events_per_wakeup < 2.0 {
+                        println!(
+                            "    about one event per wakeup: events come slower than they are read"
+                        );
+                    }
+                    if options.inotify_batch_stats {
+                        for (size, batches) in &stats.histogram {
+                            println!("    <= {size:>5} events: {batches} reads");
+                        }
+                    }
+                }
+            }
+
+            // sync inotify and readdir sync at the same time
+            if let Some((inotify, read_dir)) = runner
+                .run("read_dir_concurrent_inotify_readdir", || {
+                    read_dir_concurrent_inotify_readdir(&dir, max_files)
+                })
+            {
+                let rate = |d: Duration| max_files as f64 / d.as_secs_f64();
+                println!(
+                    "read_dir_concurrent_inotify_readdir: read_inotify {:.0} events/s, read_dir {:.0} entries/s, combined {:.0}/s",
+                    rate(inotify),
+                    rate(read_dir),
+                    2.0 * rate(inotify.max(read_dir))
+                );
+            }
+
+            // sync inotify, several instances watching the directory in their own thread
+            for instances in INOTIFY_INSTANCES {
+                let name = format!("read_inotify_multi_instance_{instances}");
+                if let Some(durations) = runner.run(&name, || {
+                    read_inotify_multi_instance(&dir, max_files, instances)
+                }) {
+                    let per_instance = max_files / instances;
+                    let rates: Vec<_> = durations
+                        .iter()
+                        .map(|d| format!("{:.0}", per_instance as f64 / d.as_secs_f64()))
+                        .collect();
+                    println!("{name}: events/s per instance: {}", rates.join(", "));
+                }
+            }
+
+            // sync inotify, events processed by chunks
+            for chunk in INOTIFY_CHUNK_SIZES {
+                let name = format!("read_inotify_chunked_{chunk}");
+                if let Some(chunks) =
+                    runner.run(&name, || read_inotify_chunked(&dir, max_files, chunk))
+                {
+                    println!("{name}: {chunks} chunks");
+                }
+            }
+
+            // sync inotify, filtered by file name
+            runner.run("read_inotify_filter", || {
+                read_inotify_filter(&dir, max_files, INOTIFY_PATTERN)
+            });
+
+            // fanotify, only counting the files written by this process. inotify
+            // doesn't report the PID: without fanotify, files are filtered by name
+            #[cfg(target_os = "linux")]
+            match fanotify_init() {
+                Ok(_) => {
+                    let pid = std::process::id() as i32;
+                    runner.run("read_fanotify_pid_filter", || {
+                        read_fanotify_pid_filter(&dir, max_files, pid).unwrap()
+                    });
+                }
+                Err(e) => {
+                    println!("fanotify not available ({e}), filtering inotify events by file name");
+                    let pattern = format!("{}*", file_name_prefix(&options.create));
+                    runner.run("read_inotify_prefix_filter", || {
+                        read_inotify_filter(&dir, max_files, &pattern)
+                    });
+                }
+            }
+
+            // sync inotify, watching the directory again if it is recreated
+            if let Some(reconnects) = runner.run("read_inotify_reconnect", || {
+                read_inotify_reconnect(&dir, max_files)
+            }) {
+                if reconnects > 0 {
+                    println!("read_inotify_reconnect: {reconnects} reconnects");
+                }
+            }
+
+            // sync inotify, stopped after some time without event
+            if let Some(count) = runner.run("read_inotify_with_idle_timeout", || {
+                read_inotify_with_idle_timeout(
+                    &dir,
+                    max_files,
+                    Duration::from_millis(INOTIFY_IDLE_TIMEOUT_MS),
+                )
+            }) {
+                if count < max_files {
+                    println!("read_inotify_with_idle_timeout: idle after {count} events");
+                }
+            }
+
+            // async / tokio inotify, several directories, each filled by its own
+            // writer thread, watched by one instance or one instance per directory
+            if runner.is_selected("read_inotify_async_multi")
+                || runner.is_selected("read_inotify_async_per_dir")
+            {
+                let mut multi_dirs = vec![dir.clone()];
+                let mut writers = Vec::new();
+                for i in 1..INOTIFY_MULTI_DIRS {
+                    let multi_dir = format!("{dir}.multi{i}");
+                    std::fs::create_dir_all(&multi_dir).unwrap();
+
+                    let (tx, rx) = std::sync::mpsc::channel();
+                    let thread_dir = multi_dir.clone();
+                    let thread_options = options.create.clone();
+                    let writer =
+                        std::thread::spawn(|| create_files(rx, thread_dir, thread_options));
+
+                    multi_dirs.push(multi_dir);
+                    writers.push((tx, writer));
+                }
+                let multi_dirs: Vec<&str> = multi_dirs.iter().map(String::as_str).collect();
+
+                if let Some(counts) = runner.run("read_inotify_async_multi", || {
+                    read_inotify_async_multi(&multi_dirs, max_files)
+                }) {
+                    println!("read_inotify_async_multi: events per directory {counts:?}");
+                }
+
+                if let Some(counts) = runner.run("read_inotify_async_per_dir", || {
+                    read_inotify_async_per_dir(&multi_dirs, max_files)
+                }) {
+                    println!("read_inotify_async_per_dir: events per directory {counts:?}");
+                }
+
+                for (tx, writer) in writers {
+                    let _ = tx.send(());
+                    writer.join().unwrap();
+                }
+                for multi_dir in &multi_dirs[1..] {
+                    std::fs::remove_dir_all(multi_dir).unwrap();
+                }
+            }
+
+            // sync inotify, ignoring the files existing before the watch. With
+            // --truncate, all the names exist once the slots are created
+            if options.create.truncate.is_none() {
+                if let Some(ignored) = runner.run("read_inotify_suppress_initial", || {
+                    read_inotify_suppress_initial(&dir, max_files).unwrap()
+                }) {
+                    println!(
+                        "read_inotify_suppress_initial: {ignored} events of existing files ignored"
+                    );
+                }
+            } else if runner.is_selected("read_inotify_suppress_initial") {
+                println!("read_inotify_suppress_initial skipped with --truncate");
+            }
+
+            // sync inotify, checking that the file names are valid UTF-8
+            if let Some(invalid) = runner.run("read_inotify_utf8_check", || {
+                read_inotify_utf8_check(&dir, max_files)
+            }) {
+                println!("read_inotify_utf8_check: {invalid} file names not valid UTF-8");
+            }
+
+            // sync inotify, the directory and an idle config directory watched
+            // with different masks by the same instance
+            if runner.is_selected("read_inotify_heterogeneous") {
+                let config_dir = format!("{dir}.config");
+                std::fs::create_dir_all(&config_dir).unwrap();
+                let watches = [
+                    (dir.clone(), WatchMask::CLOSE_WRITE),
+                    (
+                        config_dir.clone(),
+                        WatchMask::ATTRIB | WatchMask::CLOSE_WRITE,
+                    ),
+                ];
+                if let Some(counts) = runner.run("read_inotify_heterogeneous", || {
+                    read_inotify_heterogeneous(&watches, max_files)
+                }) {
+                    println!("read_inotify_heterogeneous: events per directory {counts:?}");
+                }
+                std::fs::remove_dir_all(&config_dir).unwrap();
+            }
+
+            // sync inotify, newest events of each read processed first
+            if let Some(duplicates) = runner.run("read_inotify_priority", || {
+                read_inotify_priority(&dir, max_files)
+            }) {
+                println!("read_inotify_priority: {duplicates} duplicate events dropped");
+            }
+
+            // sync inotify, debounced
+            runner.run("read_inotify_debounce", || {
+                read_inotify_debounce(&dir, max_files, INOTIFY_DEBOUNCE_MS)
+            });
+
+            // async / tokio inotify
+            if let Some(overflow) =
+                runner.run("read_inotify_async", || read_inotify_async(&dir, max_files))
+            {
+                overflow.print("read_inotify_async", max_files);
+            }
+        }
+
+        // directory entry count kept up to date by inotify events
+        if runner.is_selected("read_dir_count_cached") {
+            let mut cache = DirCountCache::new(&dir).unwrap();
+            if let Some((count, processed)) = runner.run("read_dir_count_cached", || {
+                let processed = cache.processed;
+                let mut count = 0;
+                for _ in 0..COUNT_QUERIES {
+                    count = cache.count().unwrap();
+                }
+                (count, cache.processed - processed)
+            }) {
+                // only the events and rescans of the queries are processed
+                runner.set_last_files(processed);
+                println!(
+                    "read_dir_count_cached: {count} entries after {COUNT_QUERIES} queries, {processed} events or entries processed"
+                );
+            }
+        }
+
+        // same queries as read_dir_count_cached, each one a full readdir
+        if let Some((count, processed)) = runner.run("read_dir_count", || {
+            let mut count = 0;
+            let mut processed = 0;
+            for _ in 0..COUNT_QUERIES {
+                count = std::fs::read_dir(&dir).unwrap().count();
+                processed += count;
+            }
+            (count, processed)
+        }) {
+            runner.set_last_files(processed);
+            println!("read_dir_count: {count} entries after {COUNT_QUERIES} queries");
+        }
+
+        // File System Events of macOS
+        #[cfg(target_os = "macos")]
+        runner.run("read_dir_fsevents", || read_dir_fsevents(&dir, max_files));
+
+        // sync inotify, several event types
+        let mask =
+            WatchMask::CREATE | WatchMask::MODIFY | WatchMask::CLOSE_WRITE | WatchMask::DELETE;
+        if let Some(breakdown) = runner.run("read_inotify_multi_event", || {
+            read_inotify_multi_event(&dir, max_files, mask)
+        }) {
+            print!("read_inotify_multi_event:\n{breakdown}");
+        }
+
+        // sync inotify, renames of the files written under a temporary name
+        if options.create.create_rename {
+            if let Some((renames, unpaired, overflow)) =
+                runner.run("read_inotify_move", || read_inotify_move(&dir, max_files))
+            {
+                println!("read_inotify_move: {renames} renames, {unpaired} unpaired events");
+                overflow.print("read_inotify_move", max_files);
+            }
+        } else if runner.is_selected("read_inotify_move") {
+            println!("read_inotify_move skipped without --create-rename");
+        }
+
+        // sync inotify, all event types, to see which ones the writer generates
+        if let Some(breakdown) = runner.run("read_inotify_all_events", || {
+            read_inotify_multi_event(&dir, max_files, WatchMask::ALL_EVENTS)
+        }) {
+            print!("read_inotify_all_events:\n{breakdown}");
+        }
+
+        // async / tokio inotify, stopped by SIGINT or SIGTERM. Last strategy: once
+        // tokio handles these signals, they don't terminate the process anymore
+        if !options.create.hardlink {
+            runner.run("read_inotify_graceful", || {
+                read_inotify_graceful(&dir, max_files)
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates an empty directory for the test `name`
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("read_dir_perf_{name}_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn read_dir_sorted_keeps_files_with_same_mtime() {
+        let dir = test_dir("same_mtime");
+        let mtime = filetime::FileTime::from_unix_time(1_700_000_000, 0);
+
+        for i in 0..10 {
+            let path = dir.join(format!("file{i}.txt"));
+            std::fs::write(&path, b"Hello, world!").unwrap();
+            filetime::set_file_mtime(&path, mtime).unwrap();
+        }
+
+        let sorted = read_dir_sorted(dir.to_str().unwrap(), 10).unwrap();
+
+        assert_eq!(sorted.len(), 1);
+        let mut files: Vec<_> = sorted.values().flatten().cloned().collect();
+        files.sort();
+        let mut expected: Vec<_> = (0..10).map(|i| dir.join(format!("file{i}.txt"))).collect();
+        expected.sort();
+        assert_eq!(files, expected);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Checks `read_dir_sorted` against a reference sort of the same `max`
+    /// entries with `sort_by_key`. Files with the same mtime may be in any order.
+    fn read_dir_sorted_validate(dir: &str, max: usize) -> bool {
+        let sorted: Vec<(u128, PathBuf)> = read_dir_sorted(dir, max)
+            .unwrap()
+            .into_iter()
+            .flat_map(|(duration_nano, paths)| {
+                paths.into_iter().map(move |path| (duration_nano, path))
+            })
+            .collect();
+
+        let mut reference = Vec::with_capacity(max);
+        while reference.len() < max {
+            for entry in std::fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                reference.push((modified_nanos(&path).unwrap(), path));
+
+                if reference.len() == max {
+                    break;
+                }
+            }
+        }
+        reference.sort_by_key(|(duration_nano, _)| *duration_nano);
+
+        let mtimes =
+            |files: &[(u128, PathBuf)]| files.iter().map(|(mtime, _)| *mtime).collect::<Vec<_>>();
+        if mtimes(&sorted) != mtimes(&reference) {
+            return false;
+        }
+
+        // same files for each mtime
+        let mut sorted = sorted;
+        sorted.sort();
+        reference.sort();
+        sorted == reference
+    }
+
+    #[test]
+    fn read_dir_sorted_matches_reference_sort() {
+        let dir = test_dir("sorted_validate");
+
+        // distinct mtimes, not in the creation order, and some ties
+        for i in 0..50 {
+            let path = dir.join(format!("file{i}.txt"));
+            std::fs::write(&path, b"Hello, world!").unwrap();
+            let mtime =
+                filetime::FileTime::from_unix_time(1_700_000_000 + (i * 7919) % 31, i as u32);
+            filetime::set_file_mtime(&path, mtime).unwrap();
+        }
+        for i in 50..60 {
+            let path = dir.join(format!("file{i}.txt"));
+            std::fs::write(&path, b"Hello, world!").unwrap();
+            filetime::set_file_mtime(&path, filetime::FileTime::from_unix_time(1_700_000_000, 0))
+                .unwrap();
+        }
+
+        let dir = dir.to_str().unwrap();
+        assert!(read_dir_sorted_validate(dir, 60));
+        // entries read several times
+        assert!(read_dir_sorted_validate(dir, 150));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn read_dir_sorted_with_index_matches_map() {
+        let dir = test_dir("sorted_with_index");
+        for i in 0..20 {
+            let path = dir.join(format!("file{i}.txt"));
+            std::fs::write(&path, b"Hello, world!").unwrap();
+            let mtime = filetime::FileTime::from_unix_time(1_700_000_000 + i % 5, 0);
+            filetime::set_file_mtime(&path, mtime).unwrap();
+        }
+
+        let (sorted, index) = read_dir_sorted_with_index(dir.to_str().unwrap(), 20).unwrap();
+
+        // each file of the map is indexed under its key, and only them
+        assert_eq!(index.len(), 20);
+        assert_eq!(sorted.values().map(VecDeque::len).sum::<usize>(), 20);
+        for (duration_nano, paths) in &sorted {
+            for path in paths {
+                assert_eq!(index.get(path), Some(duration_nano));
+            }
+        }
+        for i in 0..20 {
+            let path = dir.join(format!("file{i}.txt"));
+            let expected = (1_700_000_000 + i as u128 % 5) * 1_000_000_000;
+            assert_eq!(index[&path], expected);
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_dir_sorted_tiebreak_orders_same_mtime_by_inode() {
+        let dir = test_dir("tiebreak");
+        let mtime = filetime::FileTime::from_unix_time(1_700_000_000, 0);
+
+        for i in 0..10 {
+            let path = dir.join(format!("file{i}.txt"));
+            std::fs::write(&path, b"Hello, world!").unwrap();
+            filetime::set_file_mtime(&path, mtime).unwrap();
+        }
+
+        let sorted = read_dir_sorted_tiebreak(dir.to_str().unwrap(), 10).unwrap();
+        let files: Vec<_> = sorted.values().cloned().collect();
+
+        let mut expected: Vec<_> = (0..10).map(|i| dir.join(format!("file{i}.txt"))).collect();
+        expected.sort_by_key(|path| std::fs::metadata(path).unwrap().ino());
+        assert_eq!(files, expected);
+
+        // same order on each read
+        for _ in 0..3 {
+            let sorted = read_dir_sorted_tiebreak(dir.to_str().unwrap(), 10).unwrap();
+            assert_eq!(sorted.into_values().collect::<Vec<_>>(), expected);
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn read_dir_largefile_reads_directory() {
+        let dir = test_dir("largefile");
+        for i in 0..5 {
+            std::fs::write(dir.join(format!("file{i}.txt")), b"Hello, world!").unwrap();
+        }
+
+        let dir = dir.to_str().unwrap();
+        read_dir_largefile(dir, 5).unwrap();
+        // more entries than files, the directory is read again
+        read_dir_largefile(dir, 12).unwrap();
+
+        let missing = format!("{dir}/missing");
+        let e = read_dir_largefile(&missing, 5).unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::NotFound);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn read_dir_async_collect_returns_errors() {
+        let dir = test_dir("async_collect");
+        let missing = dir.join("missing");
+        let rt = Runtime::new().unwrap();
+
+        let result = rt.block_on(read_dir_async_collect(missing.to_str().unwrap(), 10));
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::NotFound);
+
+        std::fs::write(dir.join("file1.txt"), b"Hello, world!").unwrap();
+        let files = rt
+            .block_on(read_dir_async_collect(dir.to_str().unwrap(), 10))
+            .unwrap();
+        assert_eq!(files, vec![dir.join("file1.txt")]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_dir_tokio_returns_open_errors() {
+        let dir = test_dir("tokio_open_error");
+        let missing = dir.join("missing").to_str().unwrap().to_string();
+
+        let result = read_dir_tokio(&missing, 10);
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::NotFound);
+
+        let result = read_dir_tokio_work_stealing(&missing, 10);
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::NotFound);
+
+        let result = read_dir_tokio_spawn_per_entry(&missing, 10);
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::NotFound);
+
+        let result = read_dir_tokio_timeout(&missing, 10, ENTRY_TIMEOUT_MS);
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::NotFound);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn name_number_uses_sentinel_without_number() {
+        assert_eq!(name_number(Path::new("/tmp/file_0000042.txt")), 42);
+        assert_eq!(name_number(Path::new("file1000000.txt")), 1_000_000);
+        assert_eq!(name_number(Path::new("fichier_7_文件_📄.txt")), 7);
+        assert_eq!(name_number(Path::new("file.txt")), NO_NUMBER_KEY);
+        assert_eq!(name_number(Path::new("file")), NO_NUMBER_KEY);
+        assert_eq!(
+            name_number(Path::new("file99999999999999999999999.txt")),
+            NO_NUMBER_KEY
+        );
+    }
+
+    #[test]
+    fn read_dir_sparse_sorted_orders_by_name_number() {
+        let dir = test_dir("sparse_sorted");
+        for name in ["file_1000000.txt", "file_0000001.txt", "no_number.txt"] {
+            std::fs::write(dir.join(name), b"Hello, world!").unwrap();
+        }
+
+        let sorted = read_dir_sparse_sorted(dir.to_str().unwrap(), 3).unwrap();
+        let files: Vec<_> = sorted.values().flatten().cloned().collect();
+
+        assert_eq!(
+            files,
+            vec![
+                dir.join("file_0000001.txt"),
+                dir.join("file_1000000.txt"),
+                dir.join("no_number.txt"),
+            ]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_dir_sorted_by_name_length_keeps_names_of_same_length() {
+        let dir = test_dir("name_length");
+        for name in ["a.txt", "b.txt", "long_name.txt"] {
+            std::fs::write(dir.join(name), b"Hello, world!").unwrap();
+        }
+
+        let sorted = read_dir_sorted_by_name_length(dir.to_str().unwrap(), 3).unwrap();
+
+        assert_eq!(sorted.keys().copied().collect::<Vec<_>>(), vec![5, 13]);
+        let mut short: Vec<_> = sorted[&5].iter().cloned().collect();
+        short.sort();
+        assert_eq!(short, vec![dir.join("a.txt"), dir.join("b.txt")]);
+        assert_eq!(sorted[&13], VecDeque::from([dir.join("long_name.txt")]));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn group_by_extension_maps_no_extension_to_empty_key() {
+        let dir = test_dir("group_by_extension");
+        for name in ["a.txt", "b.txt", "c.log", "no_extension"] {
+            std::fs::write(dir.join(name), b"Hello, world!").unwrap();
+        }
+
+        let groups = group_by_extension(dir.to_str().unwrap()).unwrap();
+
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[OsStr::new("txt")].len(), 2);
+        assert_eq!(groups[OsStr::new("log")], vec![dir.join("c.log")]);
+        assert_eq!(groups[OsStr::new("")], vec![dir.join("no_extension")]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dir_count_cache_follows_created_and_deleted_files() {
+        let dir = test_dir("count_cache");
+        for i in 0..3 {
+            std::fs::write(dir.join(format!("file{i}.txt")), b"Hello, world!").unwrap();
+        }
+
+        let mut cache = DirCountCache::new(dir.to_str().unwrap()).unwrap();
+        assert_eq!(cache.count().unwrap(), 3);
+
+        for i in 3..5 {
+            std::fs::write(dir.join(format!("file{i}.txt")), b"Hello, world!").unwrap();
+        }
+        std::fs::remove_file(dir.join("file0.txt")).unwrap();
+        assert_eq!(cache.count().unwrap(), 4);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unescape_mountinfo_decodes_octal_escapes() {
+        assert_eq!(unescape_mountinfo("/mnt/data"), b"/mnt/data");
+        assert_eq!(unescape_mountinfo("/mnt/my\\040disk"), b"/mnt/my disk");
+        assert_eq!(unescape_mountinfo("/mnt/tab\\011"), b"/mnt/tab\t");
+        assert_eq!(
+            unescape_mountinfo("/mnt/back\\134slash"),
+            b"/mnt/back\\slash"
+        );
+        // not an escape
+        assert_eq!(unescape_mountinfo("/mnt/a\\9"), b"/mnt/a\\9");
+    }
+
+    #[test]
+    fn sorted_result_serialization_round_trips() {
+        let mut sorted: BTreeMap<u128, VecDeque<PathBuf>> = BTreeMap::new();
+        sorted.insert(1, VecDeque::from([PathBuf::from("/tmp/file1.txt")]));
+        sorted.insert(
+            2,
+            VecDeque::from([
+                PathBuf::from("/tmp/file2.txt"),
+                PathBuf::from("/tmp/fichier_3_文件_📄.txt"),
+            ]),
+        );
+
+        let bytes = serialize_sorted_result(&sorted);
+        assert_eq!(deserialize_sorted_result(&bytes), sorted);
+    }
+
+    #[test]
+    fn read_dir_sorted_mmap_finds_hard_links_and_old_files() {
+        let dir = test_dir("mmap_index");
+        let files = dir.join("files");
+        std::fs::create_dir(&files).unwrap();
+        let index_file = dir.join("index.bin");
+
+        // before the epoch, then two more links to the same inode
+        let path = files.join("file1.txt");
+        std::fs::write(&path, b"Hello, world!").unwrap();
+        filetime::set_file_mtime(&path, filetime::FileTime::from_unix_time(-1_000, 0)).unwrap();
+        std::fs::hard_link(&path, files.join("file2.txt")).unwrap();
+        std::fs::hard_link(&path, files.join("file3.txt")).unwrap();
+
+        let files_str = files.to_str().unwrap();
+        assert_eq!(write_sorted_index(files_str, 3, &index_file).unwrap(), 3);
+        assert_eq!(read_dir_sorted_mmap(files_str, 3, &index_file).unwrap(), 0);
+
+        std::fs::write(files.join("file4.txt"), b"Hello, world!").unwrap();
+        assert_eq!(read_dir_sorted_mmap(files_str, 4, &index_file).unwrap(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_dir_with_lock_ends_when_all_files_are_locked() {
+        let dir = test_dir("lock");
+        let path = dir.join("file1.txt");
+        std::fs::write(&path, b"Hello, world!").unwrap();
+
+        // flock locks are per open file: this one conflicts with the strategy's
+        let locked = File::open(&path).unwrap();
+        assert_eq!(unsafe { libc::flock(locked.as_raw_fd(), libc::LOCK_EX) }, 0);
+
+        assert_eq!(read_dir_with_lock(dir.to_str().unwrap(), 3).unwrap(), 3);
+
+        drop(locked);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_dir_with_lock_retry_gives_up_on_locked_files() {
+        let dir = test_dir("lock_retry");
+        let path = dir.join("file1.txt");
+        std::fs::write(&path, b"Hello, world!").unwrap();
+
+        let locked = File::open(&path).unwrap();
+        assert_eq!(unsafe { libc::flock(locked.as_raw_fd(), libc::LOCK_EX) }, 0);
+
+        let policy = LockRetryPolicy {
+            max_retries: 2,
+            initial_backoff: Duration::from_micros(10),
+            max_backoff: Duration::from_micros(10),
+        };
+        let skipped = read_dir_with_lock_retry(dir.to_str().unwrap(), 2, &policy).unwrap();
+        assert_eq!(skipped, vec![path.clone(), path]);
+
+        drop(locked);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_dir_sorted_merge_shares_all_entries() {
+        let dir = test_dir("merge");
+        let dirs: Vec<String> = (0..3)
+            .map(|i| {
+                let merge_dir = dir.join(format!("dir{i}"));
+                std::fs::create_dir(&merge_dir).unwrap();
+                std::fs::write(merge_dir.join("file1.txt"), b"Hello, world!").unwrap();
+                merge_dir.to_str().unwrap().to_string()
+            })
+            .collect();
+        let dirs: Vec<&str> = dirs.iter().map(String::as_str).collect();
+
+        assert_eq!(read_dir_sorted_merge(&dirs, 4).unwrap(), 4);
+        assert_eq!(read_dir_sorted_merge(&[], 4).unwrap(), 0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_dir_atomic_concurrent_handles_edge_counts() {
+        let dir = test_dir("atomic_concurrent");
+        std::fs::write(dir.join("file1.txt"), b"Hello, world!").unwrap();
+        let dir_str = dir.to_str().unwrap();
+
+        assert_eq!(read_dir_atomic_concurrent(dir_str, 0, 4).unwrap(), 0);
+        assert_eq!(read_dir_atomic_concurrent(dir_str, 3, 8).unwrap(), 3);
+        assert_eq!(
+            read_dir_atomic_concurrent(dir_str, 3, 0)
+                .unwrap_err()
+                .kind(),
+            std::io::ErrorKind::InvalidInput
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_dir_concurrent_sorted_handles_edge_counts() {
+        let dir = test_dir("concurrent_sorted");
+        std::fs::write(dir.join("file1.txt"), b"Hello, world!").unwrap();
+        let dir_str = dir.to_str().unwrap();
+
+        assert!(read_dir_concurrent_sorted(dir_str, 0, 4)
+            .unwrap()
+            .is_empty());
+        let sorted = read_dir_concurrent_sorted(dir_str, 3, 8).unwrap();
+        assert_eq!(sorted.values().map(VecDeque::len).sum::<usize>(), 3);
+        assert_eq!(
+            read_dir_concurrent_sorted(dir_str, 3, 0)
+                .unwrap_err()
+                .kind(),
+            std::io::ErrorKind::InvalidInput
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_inotify_with_idle_timeout_stops_without_events() {
+        let dir = test_dir("idle_timeout");
+
+        let start = Instant::now();
+        let count =
+            read_inotify_with_idle_timeout(dir.to_str().unwrap(), 10, Duration::from_millis(50));
+        assert_eq!(count, 0);
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn shell_quote_escapes_single_quotes() {
+        let arg = OsStr::new("/tmp/it's here/read_dir_perf");
+        let quoted = shell_quote(arg);
+        assert_eq!(quoted, b"'/tmp/it'\\''s here/read_dir_perf'");
+
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(OsStr::from_bytes(
+                &[b"printf %s ", quoted.as_slice()].concat(),
+            ))
+            .output()
+            .unwrap();
+        assert_eq!(output.stdout, arg.as_bytes());
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_args_accepts_a_small_regression_threshold() {
+        let options = parse_args(args(&["--regression-threshold", "0.1", "/tmp/dir"]));
+        assert_eq!(options.regression_threshold, 0.1);
+    }
+
+    #[test]
+    #[should_panic(expected = "--regression-threshold must be positive")]
+    fn parse_args_rejects_a_zero_regression_threshold() {
+        parse_args(args(&["--regression-threshold", "0", "/tmp/dir"]));
+    }
+
+    #[test]
+    fn read_dir_sorted_persisted_prunes_deleted_files() {
+        let dir = test_dir("persisted");
+        let files = dir.join("files");
+        std::fs::create_dir(&files).unwrap();
+        let cache_file = dir.join("cache.bin");
+        std::fs::write(files.join("file1.txt"), b"Hello, world!").unwrap();
+        std::fs::write(files.join("file2.txt"), b"Hello, world!").unwrap();
+
+        let files_str = files.to_str().unwrap();
+        assert_eq!(
+            read_dir_sorted_persisted(files_str, 2, &cache_file).unwrap(),
+            2
+        );
+
+        std::fs::remove_file(files.join("file1.txt")).unwrap();
+        assert_eq!(
+            read_dir_sorted_persisted(files_str, 1, &cache_file).unwrap(),
+            0
+        );
+
+        let cached: Vec<(u128, PathBuf)> =
+            bincode::deserialize(&std::fs::read(&cache_file).unwrap()).unwrap();
+        let cached: Vec<PathBuf> = cached.into_iter().map(|(_, path)| path).collect();
+        assert_eq!(cached, vec![files.join("file2.txt")]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_baseline_reads_medians_of_json_report() {
+        let dir = test_dir("baseline");
+        let report = BenchmarkReport {
+            kernel: "Linux \"test\"\n".to_string(),
+            fuse: false,
+            results: vec![
+                BenchmarkResult {
+                    name: "read_dir".to_string(),
+                    files: 10,
+                    durations: vec![Duration::from_millis(3), Duration::from_millis(1)],
+                },
+                BenchmarkResult {
+                    name: "read_dir_sorted".to_string(),
+                    files: 10,
+                    durations: vec![Duration::from_nanos(1_234_567)],
+                },
+            ],
+        };
+
+        let path = dir.join("report.json");
+        std::fs::write(&path, report.to_json()).unwrap();
+        let baseline = read_baseline(&path).unwrap();
+
+        assert_eq!(baseline.len(), 2);
+        assert_eq!(baseline["read_dir"], Duration::from_millis(2));
+        assert_eq!(baseline["read_dir_sorted"], Duration::from_nanos(1_234_567));
+
+        let read: BenchmarkReport = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(read.kernel, report.kernel);
+        assert_eq!(read.results[0].durations, report.results[0].durations);
+
+        std::fs::write(&path, "{\"results\": [}").unwrap();
+        assert!(read_baseline(&path).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn to_csv_writes_one_row_per_strategy_with_the_kernel() {
+        let report = BenchmarkReport {
+            kernel: "Linux 6.1.0 #1 SMP \"Debian\", 2023".to_string(),
+            fuse: false,
+            results: vec![BenchmarkResult {
+                name: "read_dir".to_string(),
+                files: 10,
+                durations: vec![Duration::from_millis(1), Duration::from_millis(3)],
+            }],
+        };
+
+        let csv = report.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "name,runs,files,min_ns,median_ns,max_ns,stddev_ms,files_per_sec,filesystem,kernel",
+                "read_dir,2,10,1000000,2000000,3000000,1.414,5000,native,\"Linux 6.1.0 #1 SMP \"\"Debian\"\", 2023\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn latency_stats_uses_nearest_rank() {
+        let durations = (1..=1000).rev().map(Duration::from_micros).collect();
+        let stats = LatencyStats::new(durations);
+
+        assert_eq!(stats.p50, Duration::from_micros(500));
+        assert_eq!(stats.p90, Duration::from_micros(900));
+        assert_eq!(stats.p95, Duration::from_micros(950));
+        assert_eq!(stats.p99, Duration::from_micros(990));
+        assert_eq!(stats.p999, Duration::from_micros(999));
+
+        assert_eq!(LatencyStats::new(Vec::new()).p999, Duration::ZERO);
+        assert_eq!(
+            LatencyStats::new(vec![Duration::from_millis(7)]).p50,
+            Duration::from_millis(7)
+        );
+    }
+
+    #[test]
+    fn read_dir_watch_count_accuracy() {
+        const FILES: usize = 1000;
+        let dir = test_dir("watch_count");
+        let dir_str = dir.to_str().unwrap();
+
+        // watched before the first file is created
+        let inotify = Inotify::init().unwrap();
+        inotify
+            .watches()
+            .add(dir_str, WatchMask::CLOSE_WRITE)
+            .unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || tx.send(read_inotify_events(inotify, FILES)).unwrap());
+
+        let options = CreateOptions::default();
+        for count in 1..=FILES {
+            write_file(
+                &format!("{dir_str}/{}", file_name(count, &options)),
+                &options,
+            );
+        }
+
+        // read_inotify blocks forever if events were dropped
+        let overflow = rx.recv_timeout(Duration::from_secs(10));
+        let files = std::fs::read_dir(&dir).unwrap().count();
+
+        if overflow.is_err() || files != FILES {
+            let max_queued_events =
+                std::fs::read_to_string("/proc/sys/fs/inotify/max_queued_events")
+                    .unwrap_or_default();
+            println!(
+                "{files} files, max_queued_events: {}",
+                max_queued_events.trim()
+            );
+        }
+        let overflow = overflow.expect("not all the events were received");
+        assert_eq!(overflow.overflows, 0);
+        assert_eq!(files, FILES);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}